@@ -29,6 +29,8 @@ const MASK4: u32 = 15;
 const MASK6: u32 = 63;
 const MASK9: u32 = 511;
 const MASK16: u32 = 65535;
+// light of voxels that are in complete darkness, so caves are not pitch black
+const MIN_LIGHT: f32 = 0.05;
 
 struct VertexInput {
     @builtin(instance_index) instance_index: u32,
@@ -120,7 +122,7 @@ fn vertex(vertex: VertexInput) -> CustomVertexOutput {
     var normal = normal_from_id(n_id);
     var c_id = (quad_info >> 3) & MASK9;
     var face_color = color_from_id(c_id);
-    var texture_layer = quad_info >> 12;
+    var texture_layer = (quad_info >> 12) & MASK16;
     var face_light = light_from_id(n_id);
     var light = f32((quad_info >> 28) & MASK4) / f32(MASK4);
 
@@ -136,7 +138,7 @@ fn vertex(vertex: VertexInput) -> CustomVertexOutput {
     out.uv = vec2(u, v);
     out.color = face_color;
     out.texture_layer = texture_layer;
-    out.face_light = face_light;
+    out.face_light = face_light * vec4(vec3(max(light, MIN_LIGHT)), 1.0);
     return out;
 }
 
@@ -1,11 +1,22 @@
-use riverbed_block_def::generate_blocks;
+use riverbed_block_def::{generate_blocks, BlockIds};
 use std::{env, error::Error, fs, path::Path};
+const BLOCK_IDS: &str = "assets/data/block_ids.ron";
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let dest_path = Path::new(&env::var_os("OUT_DIR").unwrap()).join("blocks.rs");
+    let out_dir = env::var_os("OUT_DIR").unwrap();
+    let dest_path = Path::new(&out_dir).join("blocks.rs");
     let block_def = fs::read_to_string("assets/data/blocks.def")?;
-    let rust_code = generate_blocks(&block_def)?;
+    let mut block_ids = BlockIds::from_ron(&fs::read_to_string(BLOCK_IDS)?)?;
+    let old_block_ids = block_ids.clone();
+    let rust_code = generate_blocks(&block_def, &mut block_ids)?;
     fs::write(&dest_path, rust_code)?;
+    // new blocks got an id for this build, it must be checked in so it never changes (test_block_ids_are_checked_in)
+    if block_ids != old_block_ids {
+        let ids_path = Path::new(&out_dir).join("block_ids.ron");
+        fs::write(&ids_path, block_ids.to_ron() + "\n")?;
+        println!("cargo::warning=new blocks got an id, copy {} to {BLOCK_IDS}", ids_path.display());
+    }
     println!("cargo::rerun-if-changed=assets/data/blocks.def");
+    println!("cargo::rerun-if-changed={BLOCK_IDS}");
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,6 +1,6 @@
 use std::{collections::{BTreeMap, BTreeSet}, fmt::Display};
 use itertools::Itertools;
-use crate::parse::{BlockFlag, BlockFrag, IR};
+use crate::{ids::BlockIds, parse::{BlockFlag, BlockFrag, IR}};
 
 const BLOCK_FAM: &'static str = "BlockFamily";
 const BLOCKS: &'static str = "Block";
@@ -137,6 +137,8 @@ fn generate_flags(blocks: &mut BTreeSet<BlockEntry>) -> String {
                     );
                     generated_blocks.insert(lit_furnace);
                 },
+                // generated blocks inherit the state of their block, see generate_state_impl
+                flag if flag.is_state() => {},
                 _ => {
                     let flag_name = format!("is_{:?}", flag).to_lowercase();
                     flag_fns.entry(flag_name.clone()).or_insert(MatchFn::new(&flag_name, "u32").with_default("true")).arms.push(
@@ -150,7 +152,48 @@ fn generate_flags(blocks: &mut BTreeSet<BlockEntry>) -> String {
     flag_fns.values().map(|match_fn| match_fn.to_rust(1)).join("\n\n")
 }
 
-pub fn generate(ir: &IR) -> String {
+const STATE_KIND_ENUM: &'static str = "#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum BlockStateKind {
+\t/// 0: Y, 1: X, 2: Z
+\tAxis,
+\t/// 0: Front, 1: Right, 2: Back, 3: Left
+\tFacing,
+\t/// Number of growth stages
+\tGrowth(u8),
+\t/// Number of levels
+\tLevel(u8),
+}
+";
+
+// Every block has at most one state flag, that determines how its per-voxel state is interpreted
+fn generate_state_impl(blocks: &BTreeSet<BlockEntry>) -> Result<String, String> {
+    let mut arms = Vec::new();
+    for block in blocks.iter() {
+        let states = block.flags.iter().filter(|flag| flag.is_state()).collect_vec();
+        match states.as_slice() {
+            [] => {},
+            [state] => arms.push(format!("{BLOCKS}::{block} => Some(BlockStateKind::{state:?})")),
+            _ => return Err(format!("{block} has more than one state: {states:?}")),
+        }
+    }
+    Ok(MatchFn::new("state_kind", "Option<BlockStateKind>").with_arms(arms).with_default("None").to_rust(1))
+}
+
+fn generate_id_impl(blocks: &BTreeSet<BlockEntry>, block_ids: &BlockIds) -> String {
+    let id_fn = MatchFn::new("id", "u16").with_arms(
+        blocks.iter().map(|block| format!("{BLOCKS}::{block} => {}", block_ids.get(&block.name).unwrap())).collect()
+    ).to_rust(1);
+    let from_id_arms = blocks.iter()
+        .map(|block| format!("{} => Some({BLOCKS}::{block})", block_ids.get(&block.name).unwrap()))
+        .chain(["_ => None".to_string()])
+        .join(&format!(",\n{}", tab(3)));
+    format!(
+        "{id_fn}\n\n{}pub fn from_id(id: u16) -> Option<Self> {{\n{}match id {{\n{}{from_id_arms}\n{}}}\n\t}}",
+        tab(1), tab(2), tab(3), tab(2)
+    )
+}
+
+pub fn generate(ir: &IR, block_ids: &mut BlockIds) -> Result<String, String> {
     let mut blocks: BTreeSet<BlockEntry> = BTreeSet::new();
     for block_pattern in ir.decl.iter() {
         let families = block_pattern.0.0.iter().filter_map(|frag| match frag { 
@@ -172,6 +215,8 @@ pub fn generate(ir: &IR) -> String {
         }
     }
     let flag_code = generate_flags(&mut blocks);
+    let state_code = generate_state_impl(&blocks)?;
+    block_ids.assign(blocks.iter().map(|block| block.name.as_str()));
     let mut code_blocks = Vec::new();
     code_blocks.push("use serde::{Deserialize, Serialize};".to_string());
     code_blocks.push("use strum_macros::{EnumIter, EnumString, Display};".to_string());
@@ -181,9 +226,12 @@ pub fn generate(ir: &IR) -> String {
         code_blocks.push(generate_enum(family, variants));
     }
     code_blocks.push(generate_enum(BLOCKS, &blocks));
+    code_blocks.push(STATE_KIND_ENUM.to_string());
     code_blocks.push(format!("impl {BLOCKS} {{"));
     code_blocks.push(flag_code);
+    code_blocks.push(state_code);
     code_blocks.push(generate_family_impl(&blocks));
+    code_blocks.push(generate_id_impl(&blocks, block_ids));
     code_blocks.push("}".to_string());
-    code_blocks.join("\n")
+    Ok(code_blocks.join("\n"))
 }
\ No newline at end of file
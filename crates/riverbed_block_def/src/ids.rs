@@ -0,0 +1,68 @@
+use std::collections::{BTreeMap, BTreeSet};
+use ron::ser::PrettyConfig;
+
+/// Persisted mapping from block names to numeric ids.
+/// Ids are never reassigned, even if the block is removed from the definition file,
+/// so saves and network packets keep their meaning when blocks.def is edited.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BlockIds(BTreeMap<String, u16>);
+
+impl BlockIds {
+    pub fn from_ron(content: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(content).map(BlockIds)
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(&self.0, PrettyConfig::default()).unwrap()
+    }
+
+    pub fn get(&self, block: &str) -> Option<u16> {
+        self.0.get(block).copied()
+    }
+
+    /// Gives an id to every block that doesn't have one yet, Air always gets 0 if it's new
+    pub(crate) fn assign<'a>(&mut self, blocks: impl Iterator<Item = &'a str>) {
+        let new_blocks = blocks
+            .filter(|block| !self.0.contains_key(*block))
+            .collect::<BTreeSet<_>>();
+        if new_blocks.contains("Air") && !self.0.values().any(|id| *id == 0) {
+            self.0.insert("Air".to_string(), 0);
+        }
+        let mut next_id = self.0.values().max().map(|id| id + 1).unwrap_or(0);
+        for block in new_blocks {
+            if self.0.contains_key(block) {
+                continue;
+            }
+            self.0.insert(block.to_string(), next_id);
+            next_id += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlockIds;
+
+    #[test]
+    fn test_assign_keeps_ids() {
+        let mut ids = BlockIds::default();
+        ids.assign(["Dirt", "Air", "Granite"].into_iter());
+        assert_eq!(ids.get("Air"), Some(0));
+        let dirt = ids.get("Dirt");
+        let granite = ids.get("Granite");
+        ids.assign(["Clay", "Dirt", "Air", "Granite"].into_iter());
+        assert_eq!(ids.get("Dirt"), dirt);
+        assert_eq!(ids.get("Granite"), granite);
+        assert_eq!(ids.get("Clay"), Some(3));
+    }
+
+    #[test]
+    fn test_removed_ids_are_not_reused() {
+        let mut ids = BlockIds::default();
+        ids.assign(["Air", "Dirt"].into_iter());
+        let mut ids = BlockIds::from_ron(&ids.to_ron()).unwrap();
+        ids.assign(["Air", "Clay"].into_iter());
+        assert_eq!(ids.get("Dirt"), Some(1));
+        assert_eq!(ids.get("Clay"), Some(2));
+    }
+}
@@ -1,11 +1,14 @@
 mod parse;
 mod gen;
+mod ids;
 use gen::generate;
 use parse::parse_file;
+pub use ids::BlockIds;
 
 
-pub fn generate_blocks(block_def: &str) -> Result<String, std::io::Error> {
+/// Generates the Block code, `block_ids` is updated with ids for the new blocks
+pub fn generate_blocks(block_def: &str, block_ids: &mut BlockIds) -> Result<String, std::io::Error> {
     let (_, ir) = parse_file(block_def).map_err(|e| std::io::Error::other(e.to_owned()))?;
-    let code = generate(&ir);
+    let code = generate(&ir, block_ids).map_err(std::io::Error::other)?;
     Ok(code)
 }
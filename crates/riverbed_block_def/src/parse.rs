@@ -17,7 +17,18 @@ pub(crate) enum BlockFrag {
 pub enum BlockFlag {
     Renewable(u32),
     Transparent,
-    Furnace(u32)
+    Furnace(u32),
+    // per-voxel states, a block can have at most one of them
+    Axis,
+    Facing,
+    Growth(u8),
+    Level(u8),
+}
+
+impl BlockFlag {
+    pub fn is_state(&self) -> bool {
+        matches!(self, BlockFlag::Axis | BlockFlag::Facing | BlockFlag::Growth(_) | BlockFlag::Level(_))
+    }
 }
 
 impl FromStr for BlockFlag {
@@ -188,4 +199,14 @@ mod tests {
         let (_, ir) = parse_decl(blockdef).unwrap();
         assert_eq!(ir, AddBlock((vec![BlockFrag::Ident("IronOre".to_string())], BTreeSet::from([BlockFlag::Renewable(10)]))));
     }
+
+    #[test]
+    fn test_parse_state_flags() {
+        let blockdef = r#"block {Wood}{Log} axis"#;
+        let (_, ir) = parse_decl(blockdef).unwrap();
+        assert_eq!(ir.0.1, BTreeSet::from([BlockFlag::Axis]));
+        let blockdef = r#"block Campfire furnace(600) facing"#;
+        let (_, ir) = parse_decl(blockdef).unwrap();
+        assert_eq!(ir.0.1, BTreeSet::from([BlockFlag::Furnace(600), BlockFlag::Facing]));
+    }
 }
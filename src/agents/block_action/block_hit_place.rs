@@ -1,13 +1,12 @@
 use std::fs;
 use std::iter::zip;
-use std::time::{Duration, Instant};
 use crate::items::{BlockLootTable, DropQuantity, FiringTable, InventoryTrait, Item, LootEntry, Stack};
 use crate::render::FpsCam;
 use crate::sounds::ItemGet;
 use crate::ui::{ControllingPlayer, GameUiState, ItemHolder, SelectedHotbarSlot};
 use crate::Block;
-use crate::world::{BlockPos, BlockEntities, Realm, VoxelWorld};
-use crate::agents::{TargetBlock, Action, PlayerControlled};
+use crate::world::{BlockChangeCause, BlockPos, BlockTick, BlockTicked, CollisionBox, EditJournal, Realm, ScheduledTicks, VoxelWorld, WorldClock};
+use crate::agents::{TargetBlock, Action, PlayerControlled, AABB};
 use crate::WorldRng;
 use leafwing_input_manager::prelude::*;
 use bevy::prelude::*;
@@ -30,6 +29,7 @@ impl Plugin for BlockHitPlacePlugin {
 			.add_systems(Update, block_outline.run_if(in_state(ControllingPlayer)))
             .add_systems(Update, place_block.run_if(in_state(GameUiState::None)))
             .add_systems(Update, renew_block)
+            .add_systems(Update, undo_redo.run_if(in_state(GameUiState::None)))
 			;
     }
 }
@@ -69,15 +69,6 @@ const EDGES_LINES: [Vec3; 4] = [
     Vec3::new(1., -1., -1.), 
 ];
 
-#[derive(Component)]
-pub struct BlockAttached(BlockPos);
-
-#[derive(Component)]
-pub struct Renewable {
-    // TODO: fine for now but it's better if the world has its own clock
-    renew_after: Instant,
-}
-
 fn target_block(
     mut player: Query<(&mut TargetBlock, &Realm), With<PlayerControlled>>, 
     player_cam: Query<&GlobalTransform, With<FpsCam>>,
@@ -123,9 +114,9 @@ fn break_action(
     block_break_table: Res<BlockBreakTable>,
     block_harvest_table: Res<BlockHarvestTable>,
     time: Res<Time>,
-    mut col_entities: ResMut<BlockEntities>,
+    clock: Res<WorldClock>,
+    mut block_ticks: ResMut<ScheduledTicks>,
     mut world_rng: ResMut<WorldRng>,
-    block_entt_query: Query<&BlockAttached>,
 ) {
     for (player, target_block_opt, mut hotbar, action, opt_looting) in block_action_query.iter_mut() {
         let Some(mut looting) = opt_looting else {
@@ -178,24 +169,14 @@ fn break_action(
         };
         match looting.action_type {
             BlockActionType::Breaking => {
-                world.set_block(target_block.pos, Block::Air);
-                if let Some(entity) = col_entities.get(&target_block.pos) {
-                    if let Ok(block_pos) = block_entt_query.get(entity) {
-                        if block_pos.0 == target_block.pos {
-                            commands.entity(entity).despawn();
-                        }
-                    }
-                }
+                world.set_block(target_block.pos, Block::Air, BlockChangeCause::Player);
+                block_ticks.cancel(target_block.pos);
             }
             BlockActionType::Harvesting => {
                 let depleted = world.get_block(target_block.pos).depleted();
-                world.set_block(target_block.pos, depleted);
+                world.set_block(target_block.pos, depleted, BlockChangeCause::Player);
                 if let Some(renewal_minutes) = depleted.renewal_minutes() {
-                    let renew_entt = commands.spawn((
-                        Renewable { renew_after: Instant::now().checked_add(Duration::from_secs(renewal_minutes as u64)).unwrap() }, 
-                        BlockAttached(target_block.pos)
-                    )).id();
-                    col_entities.add(&target_block.pos, renew_entt);    
+                    block_ticks.schedule(target_block.pos, clock.tick + WorldClock::minutes(renewal_minutes), BlockTick::Renew);
                 }
             }
         }
@@ -226,6 +207,7 @@ fn place_block(
     mut commands: Commands,
     world: Res<VoxelWorld>, 
     mut block_action_query: Query<(&TargetBlock, &mut ItemHolder, &ActionState<Action>)>, 
+    bodies: Query<(&Transform, &Realm, &AABB)>,
     selected_slot: Res<SelectedHotbarSlot>
 ) {
     for (target_block_opt, mut hotbar, action) in block_action_query.iter_mut() {
@@ -246,25 +228,45 @@ fn place_block(
                 continue;
             }
         };
-        if !world.set_block_safe(pos, block) {
+        // Blocks can't be placed inside of an entity
+        if let Some(block_box) = block.collision_box().map(|block_box| block_box.translated(pos.into())) {
+            if bodies.iter().any(|(transform, realm, aabb)| 
+                *realm == pos.realm && block_box.overlaps(&CollisionBox::new(transform.translation, aabb.0))
+            ) {
+                hotbar.get_mut(selected_slot.0).try_add(Stack::Some(Item::Block(block), 1));
+                continue;
+            }
+        }
+        if !world.set_block_safe(pos, block, BlockChangeCause::Player) {
             // If the block couldn't be added we add it back
             hotbar.get_mut(selected_slot.0).try_add(Stack::Some(Item::Block(block), 1));
         } else {
+            // oriented blocks face the block they're placed against
+            world.set_state(pos, block.placed_state(target_block.normal));
             commands.trigger(BlockPlaced(pos));
         }
     }
 }
 
-fn renew_block(
-    mut commands: Commands,
+fn renew_block(world: Res<VoxelWorld>, mut ev_ticked: EventReader<BlockTicked>) {
+    for ticked in ev_ticked.read() {
+        if ticked.tick == BlockTick::Renew {
+            world.set_block(ticked.pos, world.get_block(ticked.pos).renewed(), BlockChangeCause::Tick);
+        }
+    }
+}
+
+fn undo_redo(
     world: Res<VoxelWorld>,
-    renewables: Query<(Entity, &Renewable, &BlockAttached)>,
+    mut journal: ResMut<EditJournal>,
+    action_query: Query<&ActionState<Action>, With<PlayerControlled>>,
 ) {
-    let now = Instant::now();
-    for (entity, renewable, pos) in renewables.iter() {
-        if now >= renewable.renew_after {
-            world.set_block(pos.0, world.get_block(pos.0).renewed());
-            commands.entity(entity).despawn();
-        }
+    let Ok(action) = action_query.get_single() else {
+        return;
+    };
+    if action.just_pressed(&Action::Undo) {
+        journal.undo(&world);
+    } else if action.just_pressed(&Action::Redo) {
+        journal.redo(&world);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,188 @@
+use std::str::FromStr;
+use bevy::prelude::*;
+use crate::items::BlockKind;
+use crate::ui::{CommandOutput, CommandSubmitted};
+use crate::world::{BlockChangeCause, BlockPos, EditOp, EditShape, Realm, Schematic, VoxelWorld};
+use crate::agents::PlayerControlled;
+use crate::Block;
+
+pub struct EditCommandPlugin;
+
+impl Plugin for EditCommandPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(Clipboard(None))
+            .add_systems(Update, run_edit_commands);
+    }
+}
+
+const SCHEMATICS_DIR: &str = "assets/schematics";
+
+/// The last copied or loaded schematic
+#[derive(Resource)]
+struct Clipboard(Option<Schematic>);
+
+// "12" is absolute, "~" and "~-3" are relative to the player
+fn parse_coord(arg: &str, origin: i32) -> Result<i32, String> {
+    let (relative, value) = match arg.strip_prefix('~') {
+        Some("") => return Ok(origin),
+        Some(value) => (true, value),
+        None => (false, arg),
+    };
+    let value = value.parse::<i32>().map_err(|_| format!("'{arg}' is not a coordinate"))?;
+    Ok(if relative { origin + value } else { value })
+}
+
+fn parse_pos(args: &[&str], origin: BlockPos) -> Result<BlockPos, String> {
+    let [x, y, z] = args else {
+        return Err("expected 3 coordinates".to_string());
+    };
+    Ok(BlockPos {
+        x: parse_coord(x, origin.x)?,
+        y: parse_coord(y, origin.y)?,
+        z: parse_coord(z, origin.z)?,
+        realm: origin.realm,
+    })
+}
+
+fn parse_block(arg: &str) -> Result<Block, String> {
+    Block::from_str(arg).map_err(|_| format!("unknown block '{arg}'"))
+}
+
+fn parse_f32(arg: &str) -> Result<f32, String> {
+    arg.parse().map_err(|_| format!("'{arg}' is not a number"))
+}
+
+/// Parses commands such as:
+/// - `fill x1 y1 z1 x2 y2 z2 Block`
+/// - `hollow x1 y1 z1 x2 y2 z2 Block`
+/// - `replace x1 y1 z1 x2 y2 z2 BlockOrFamily Block`
+/// - `sphere x y z radius Block`
+/// - `cylinder x y z radius height Block`
+/// - `line x1 y1 z1 x2 y2 z2 Block`
+pub fn parse_edit_command(command: &str, origin: BlockPos) -> Result<(EditShape, EditOp), String> {
+    let args: Vec<&str> = command.split_whitespace().collect();
+    match args.as_slice() {
+        ["fill", corners @ .., block] if corners.len() == 6 => Ok((
+            EditShape::Cuboid(parse_pos(&corners[..3], origin)?, parse_pos(&corners[3..], origin)?),
+            EditOp::Fill(parse_block(block)?)
+        )),
+        ["hollow", corners @ .., block] if corners.len() == 6 => Ok((
+            EditShape::HollowCuboid(parse_pos(&corners[..3], origin)?, parse_pos(&corners[3..], origin)?),
+            EditOp::Fill(parse_block(block)?)
+        )),
+        ["replace", corners @ .., kind, block] if corners.len() == 6 => Ok((
+            EditShape::Cuboid(parse_pos(&corners[..3], origin)?, parse_pos(&corners[3..], origin)?),
+            EditOp::Replace(
+                BlockKind::from_str(kind).map_err(|_| format!("unknown block or family '{kind}'"))?,
+                parse_block(block)?
+            )
+        )),
+        ["sphere", center @ .., radius, block] if center.len() == 3 => Ok((
+            EditShape::Sphere { center: parse_pos(center, origin)?, radius: parse_f32(radius)? },
+            EditOp::Fill(parse_block(block)?)
+        )),
+        ["cylinder", base @ .., radius, height, block] if base.len() == 3 => Ok((
+            EditShape::Cylinder {
+                base: parse_pos(base, origin)?,
+                radius: parse_f32(radius)?,
+                height: height.parse().map_err(|_| format!("'{height}' is not a height"))?
+            },
+            EditOp::Fill(parse_block(block)?)
+        )),
+        ["line", ends @ .., block] if ends.len() == 6 => Ok((
+            EditShape::Line(parse_pos(&ends[..3], origin)?, parse_pos(&ends[3..], origin)?),
+            EditOp::Fill(parse_block(block)?)
+        )),
+        _ => Err(format!("unknown command '{command}'")),
+    }
+}
+
+/// Runs clipboard commands:
+/// - `copy x1 y1 z1 x2 y2 z2`, with the player position as the paste anchor
+/// - `paste`, at the player position
+/// - `rotate`, a quarter turn clockwise
+/// - `mirror x` or `mirror z`
+/// - `save name` and `load name`, from the schematics folder
+/// Returns None if the command is not a clipboard command
+fn run_clipboard_command(command: &str, origin: BlockPos, world: &VoxelWorld, clipboard: &mut Clipboard) -> Option<Result<String, String>> {
+    let args: Vec<&str> = command.split_whitespace().collect();
+    let schematic_path = |name: &str| format!("{SCHEMATICS_DIR}/{name}.schem");
+    Some(match (args.as_slice(), &clipboard.0) {
+        (["copy", corners @ ..], _) if corners.len() == 6 => (|| {
+            let schematic = Schematic::copy(world, parse_pos(&corners[..3], origin)?, parse_pos(&corners[3..], origin)?, origin);
+            let size = schematic.size;
+            clipboard.0 = Some(schematic);
+            Ok(format!("copied {}x{}x{} blocks", size[0], size[1], size[2]))
+        })(),
+        (["load", name], _) => Schematic::load(schematic_path(name)).map(|schematic| {
+            clipboard.0 = Some(schematic);
+            format!("loaded {name}")
+        }).map_err(|err| format!("couldn't load {name}: {err}")),
+        (["paste" | "rotate" | "mirror" | "save", ..], None) => Err("the clipboard is empty".to_string()),
+        (["paste"], Some(schematic)) => Ok(format!(
+            "{} blocks changed", schematic.paste(world, origin, false, BlockChangeCause::Edit)
+        )),
+        (["rotate"], Some(schematic)) => {
+            clipboard.0 = Some(schematic.rotate_y());
+            Ok("rotated".to_string())
+        },
+        (["mirror", "x"], Some(schematic)) => {
+            clipboard.0 = Some(schematic.mirror_x());
+            Ok("mirrored along x".to_string())
+        },
+        (["mirror", "z"], Some(schematic)) => {
+            clipboard.0 = Some(schematic.mirror_z());
+            Ok("mirrored along z".to_string())
+        },
+        (["save", name], Some(schematic)) => schematic.save(schematic_path(name))
+            .map(|_| format!("saved {name}"))
+            .map_err(|err| format!("couldn't save {name}: {err}")),
+        _ => return None,
+    })
+}
+
+fn run_edit_commands(
+    mut commands: EventReader<CommandSubmitted>,
+    world: Res<VoxelWorld>,
+    mut clipboard: ResMut<Clipboard>,
+    mut outputs: EventWriter<CommandOutput>,
+    player_query: Query<(&Transform, &Realm), With<PlayerControlled>>,
+) {
+    let Ok((transform, realm)) = player_query.get_single() else {
+        return;
+    };
+    let origin = BlockPos::from((transform.translation, *realm));
+    for CommandSubmitted(command) in commands.read() {
+        let res = run_clipboard_command(command, origin, &world, &mut clipboard).unwrap_or_else(||
+            parse_edit_command(command, origin).map(|(shape, op)|
+                format!("{} blocks changed", world.edit(shape, op, BlockChangeCause::Edit))
+            )
+        );
+        let output = match res {
+            Ok(msg) => {
+                info!("/{command}: {msg}");
+                msg
+            },
+            Err(err) => {
+                warn!("/{command}: {err}");
+                err
+            },
+        };
+        outputs.send(CommandOutput(output));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_relative_fill() {
+        let origin = BlockPos { x: 10, y: 60, z: -5, realm: Realm::Overworld };
+        let (shape, op) = parse_edit_command("fill ~ ~-1 ~ 0 ~2 3 Granite", origin).unwrap();
+        assert_eq!(shape, EditShape::Cuboid(origin + (0, -1, 0), BlockPos { x: 0, y: 62, z: 3, realm: Realm::Overworld }));
+        assert_eq!(op, EditOp::Fill(Block::Granite));
+        assert!(parse_edit_command("fill ~ ~ ~ Granite", origin).is_err());
+    }
+}
@@ -1,8 +1,10 @@
 use std::fs;
 use bevy::prelude::*;
 use leafwing_input_manager::prelude::*;
-use crate::{agents::{Action, PlayerControlled, TargetBlock}, items::{FiringTable, LitFurnace, Stack}, ui::{furnace_slots, GameUiState, ItemHolder, OpenFurnace}, world::{BlockEntities, BlockPos, VoxelWorld}};
-use super::block_hit_place::BlockAttached;
+use crate::{agents::{Action, PlayerControlled, TargetBlock}, items::{FiringTable, LitFurnace, Stack}, ui::{furnace_slots, GameUiState, ItemHolder, OpenFurnace}, world::{BlockChangeCause, BlockEntities, BlockPos, BlockTick, BlockTicked, ScheduledTicks, VoxelWorld, WorldClock, TICKS_PER_SEC}};
+
+/// Lit furnaces are updated every FURNACE_TICK_SECS seconds of game time
+const FURNACE_TICK_SECS: u32 = 1;
 
 pub struct FurnaceActionPlugin;
 
@@ -80,6 +82,8 @@ fn open_furnace_menu(
 fn on_furnace_edit(
     voxel_world: Res<VoxelWorld>,
     mut commands: Commands,
+    clock: Res<WorldClock>,
+    mut block_ticks: ResMut<ScheduledTicks>,
     item_holders: Query<(Entity, &ItemHolder, &Furnace, Option<&LitFurnace>), Changed<ItemHolder>>,
     firing_table: Res<FiringTable>,
 ) {
@@ -87,7 +91,9 @@ fn on_furnace_edit(
         let Some(mut new_lit_furnace) = firing_table.get(item_holder, furnace.temp) else {
             // Turn furnace off
             commands.entity(furnace_entt).remove::<LitFurnace>();
-            voxel_world.set_block(furnace.block_pos, voxel_world.get_block(furnace.block_pos).off());
+            // lit and unlit furnaces have the same states so the facing is kept
+            let state = voxel_world.get_state(furnace.block_pos);
+            voxel_world.set_block_with_state(furnace.block_pos, voxel_world.get_block(furnace.block_pos).off(), state, BlockChangeCause::Machine);
             continue;
         };
         // If firing continues we inherit the previous remaining fuel sec
@@ -98,33 +104,124 @@ fn on_furnace_edit(
         }
         // Replace the previous value
         commands.entity(furnace_entt).insert(new_lit_furnace);
-        voxel_world.set_block(furnace.block_pos, voxel_world.get_block(furnace.block_pos).on());
+        if block_ticks.get(furnace.block_pos) != Some(BlockTick::Furnace) {
+            block_ticks.schedule(furnace.block_pos, clock.tick + WorldClock::secs(FURNACE_TICK_SECS), BlockTick::Furnace);
+        }
+        let state = voxel_world.get_state(furnace.block_pos);
+        voxel_world.set_block_with_state(furnace.block_pos, voxel_world.get_block(furnace.block_pos).on(), state, BlockChangeCause::Machine);
     }
 }
 
 fn tick_furnaces(
-    mut item_holders: Query<(&mut ItemHolder, &mut LitFurnace)>,
-    time: Res<Time>
+    mut furnaces: Query<(&Furnace, &mut ItemHolder, &mut LitFurnace)>,
+    block_entities: Res<BlockEntities>,
+    clock: Res<WorldClock>,
+    firing_table: Res<FiringTable>,
+    mut block_ticks: ResMut<ScheduledTicks>,
+    mut ev_ticked: EventReader<BlockTicked>,
 ) {
-    for (mut item_holder, mut lit_furnace) in item_holders.iter_mut() {
-        if lit_furnace.fuel_sec <= 0. || lit_furnace.firing_sec <= 0. {
+    for ticked in ev_ticked.read() {
+        if ticked.tick != BlockTick::Furnace {
             continue;
         }
-        lit_furnace.fuel_sec -= time.delta_seconds();
-        lit_furnace.firing_sec -= time.delta_seconds();
-        // Early return to avoid triggering change detection
-        if lit_furnace.firing_sec > 0. && lit_furnace.fuel_sec > 0. {
+        // the furnace may have been broken since
+        let Some(entity) = block_entities.get(&ticked.pos) else {
             continue;
-        }
-        let ItemHolder::Furnace { fuel, material, output } = item_holder.as_mut() else {
+        };
+        let Ok((furnace, mut item_holder, mut lit_furnace)) = furnaces.get_mut(entity) else {
             continue;
         };
-        if lit_furnace.firing_sec <= 0. {
-            material.take(1);
-            output.try_add(Stack::Some(lit_furnace.output, 1));
+        block_ticks.schedule(ticked.pos, clock.tick + WorldClock::secs(FURNACE_TICK_SECS), BlockTick::Furnace);
+        if lit_furnace.fuel_sec <= 0. || lit_furnace.firing_sec <= 0. {
+            continue;
         }
-        if lit_furnace.fuel_sec <= 0. {
-            fuel.take(1);
+        // the tick was due FURNACE_TICK_SECS after the previous one, it's late if the column was unloaded in between
+        let ticks = clock.tick + WorldClock::secs(FURNACE_TICK_SECS) - ticked.due;
+        let mut secs = ticks as f32 / TICKS_PER_SEC as f32;
+        while secs > 0. {
+            let step = secs.min(lit_furnace.firing_sec).min(lit_furnace.fuel_sec);
+            secs -= step;
+            lit_furnace.fuel_sec -= step;
+            lit_furnace.firing_sec -= step;
+            // Early exit to avoid triggering change detection
+            if lit_furnace.firing_sec > 0. && lit_furnace.fuel_sec > 0. {
+                break;
+            }
+            let ItemHolder::Furnace { fuel, material, output } = item_holder.as_mut() else {
+                break;
+            };
+            if lit_furnace.firing_sec <= 0. {
+                material.take(1);
+                output.try_add(Stack::Some(lit_furnace.output, 1));
+            }
+            if lit_furnace.fuel_sec <= 0. {
+                fuel.take(1);
+            }
+            // keep firing with what's left, on_furnace_edit turns the furnace off if nothing is
+            let Some(mut next_lit_furnace) = firing_table.get(&item_holder, furnace.temp) else {
+                break;
+            };
+            if lit_furnace.fuel_sec > 0. {
+                next_lit_furnace.fuel_sec = lit_furnace.fuel_sec;
+            }
+            *lit_furnace = next_lit_furnace;
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use crate::{
+        items::{FiringTable, Item, LitFurnace, Stack}, ui::ItemHolder,
+        world::{BlockChangeCause, BlockEntities, BlockPos, BlockTick, BlockTicked, ColPos, Realm, ScheduledTicks, VoxelWorld, WorldClock},
+        Block
+    };
+    use super::{tick_furnaces, Furnace, FURNACE_TICK_SECS};
+
+    #[test]
+    fn test_furnace_catches_up_after_reload() {
+        let firing_table: FiringTable = json5::from_str(r#"{ Clay: { min_temp: 600, smelt_time: 5, output: "Brick" } }"#).unwrap();
+        let pos = BlockPos { x: -5, y: 0, z: 0, realm: Realm::Overworld };
+        let col = ColPos { x: -1, z: 0, realm: Realm::Overworld };
+        let item_holder = ItemHolder::Furnace {
+            fuel: Stack::Some(Item::Coal, 1),
+            material: Stack::Some(Item::Clay, 3),
+            output: Stack::None
+        };
+        let lit_furnace: LitFurnace = firing_table.get(&item_holder, 600).unwrap();
+        let mut app = App::new();
+        app
+            .add_event::<BlockTicked>()
+            .insert_resource(firing_table)
+            .insert_resource(ScheduledTicks::default())
+            .insert_resource(BlockEntities::default())
+            .add_systems(Update, tick_furnaces);
+        let furnace = app.world_mut().spawn((
+            Furnace { name: Block::CampfireOn.to_string(), temp: 600, block_pos: pos }, item_holder, lit_furnace
+        )).id();
+        app.world_mut().resource_mut::<BlockEntities>().add(&pos, furnace);
+        app.world_mut().resource_mut::<ScheduledTicks>().schedule(pos, WorldClock::secs(FURNACE_TICK_SECS), BlockTick::Furnace);
+        // the column unloads right away and is loaded again 13s later
+        let world = VoxelWorld::new();
+        let now = WorldClock::secs(13);
+        app.insert_resource(WorldClock::new(now));
+        assert!(app.world_mut().resource_mut::<ScheduledTicks>().pop_due(&world, now).is_empty());
+        world.set_block(pos, Block::CampfireOn, BlockChangeCause::Gen);
+        let mut ticks = app.world_mut().resource_mut::<ScheduledTicks>();
+        ticks.on_col_load(col);
+        let due = ticks.pop_due(&world, now);
+        assert_eq!(due.len(), 1);
+        app.world_mut().send_event_batch(due);
+        app.update();
+        // 2 clays were fired in 10s, the third one has 2s left
+        let ItemHolder::Furnace { fuel, material, output } = app.world().get::<ItemHolder>(furnace).unwrap() else {
+            unreachable!();
+        };
+        assert_eq!(*fuel, Stack::Some(Item::Coal, 1));
+        assert_eq!(*material, Stack::Some(Item::Clay, 1));
+        assert_eq!(*output, Stack::Some(Item::Brick, 2));
+        assert!((app.world().get::<LitFurnace>(furnace).unwrap().firing_sec - 2.).abs() < 1e-3);
+        assert_eq!(app.world().resource::<ScheduledTicks>().get(pos), Some(BlockTick::Furnace));
+    }
+}
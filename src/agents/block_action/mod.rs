@@ -1,10 +1,12 @@
 mod block_hit_place;
 mod furnace_action;
+mod edit_command;
 pub use furnace_action::*;
 pub use block_hit_place::*;
 use bevy::prelude::*;
 use block_hit_place::BlockHitPlacePlugin;
 use furnace_action::FurnaceActionPlugin;
+use edit_command::EditCommandPlugin;
 
 pub struct BlockActionPlugin;
 
@@ -13,7 +15,8 @@ impl Plugin for BlockActionPlugin {
         app
             .add_plugins((
                 BlockHitPlacePlugin,
-                FurnaceActionPlugin
+                FurnaceActionPlugin,
+                EditCommandPlugin,
             ))
         ;
     }
@@ -4,6 +4,8 @@ use crate::Block;
 use crate::world::{BlockPos, VoxelWorld, Realm};
 const FREE_FLY_Y_SPEED: f32 = 100.;
 const ACC_MULT: f32 = 150.;
+// distance kept between an entity and the blocks it collides with
+const COLLISION_GAP: f32 = 0.001;
 
 pub struct MovementPlugin;
 
@@ -70,18 +72,6 @@ fn blocks_perp_y(pos: Vec3, realm: Realm, aabb: &AABB) -> impl Iterator<Item = B
     })
 }
 
-fn blocks_perp_z(pos: Vec3, realm: Realm, aabb: &AABB) -> impl Iterator<Item = BlockPos> {
-    iproduct!(extent(pos.x, aabb.0.x) , extent(pos.y, aabb.0.y)).map(move |(x, y)| BlockPos {
-        x, y, z: pos.z.floor() as i32, realm: realm
-    })
-}
-
-fn blocks_perp_x(pos: Vec3, realm: Realm, aabb: &AABB) -> impl Iterator<Item = BlockPos> {
-    iproduct!(extent(pos.y, aabb.0.y) , extent(pos.z, aabb.0.z)).map(move |(y, z)| BlockPos {
-        x: pos.x.floor() as i32, y, z, realm: realm
-    })
-}
-
 fn update_stepped_block(blocks: Res<VoxelWorld>, mut query: Query<(&Transform, &Realm, &AABB, &mut SteppingOn)>) {
     for (transform, realm, aabb, mut stepping_on) in query.iter_mut() {
         let below = transform.translation + Vec3::new(0., -0.01, 0.);
@@ -167,6 +157,7 @@ fn apply_acc_free_fly(mut query: Query<(&Heading, &mut Velocity), With<FreeFly>>
     }
 }
 
+// the entity is moved one axis at a time so it slides along the blocks it collides with
 fn apply_velocity(
     blocks: Res<VoxelWorld>, 
     time: Res<Time>, 
@@ -176,68 +167,21 @@ fn apply_velocity(
         if !blocks.is_col_loaded(transform.translation, *realm) {
             continue;
         }
-        let applied_velocity = velocity.0*time.delta_seconds();
-        // split the motion on all 3 axis, check for collisions, adjust the final speed vector if there's any
-        // x
-        let xpos = if applied_velocity.x > 0. { aabb.0.x + transform.translation.x } else { transform.translation.x }; 
-        let mut stopped = false;
-        for x in extent(xpos, applied_velocity.x).into_iter().skip(1) {
-            let pos_x = Vec3 { x: x as f32, y: transform.translation.y, z: transform.translation.z };
-            if blocks_perp_x(pos_x, *realm, aabb).any(|pos| !blocks.get_block(pos).is_traversable()) 
-            {
-                // there's a collision in this direction, stop at the block limit
-                if applied_velocity.x > 0. { 
-                    transform.translation.x = pos_x.x - aabb.0.x - 0.001;
-                } else { 
-                    transform.translation.x = pos_x.x + 1.001;
-                }
-                velocity.0.x = 0.;
-                stopped = true;
-                break;
-            }  
+        // the entity may be stuck in blocks (they were placed on it or the terrain loaded around it), push it out the shortest way
+        if let Some(offset) = blocks.push_out_aabb(*realm, transform.translation, aabb.0) {
+            transform.translation += offset;
         }
-        if !stopped {
-            transform.translation.x += applied_velocity.x;
-        }
-        // y
-        let ypos: f32 = if applied_velocity.y > 0. { aabb.0.y + transform.translation.y } else { transform.translation.y }; 
-        let mut stopped = false;
-        for y in extent(ypos, applied_velocity.y).into_iter().skip(1) {
-            let pos_y = Vec3 {x: transform.translation.x, y: y as f32, z: transform.translation.z };
-            if blocks_perp_y(pos_y, *realm, aabb).any(|pos| !blocks.get_block(pos).is_traversable()) {
-                // there's a collision in this direction, stop at the block limit
-                if applied_velocity.y > 0. {
-                    transform.translation.y = pos_y.y - aabb.0.y - 0.001;
-                } else {
-                    transform.translation.y = pos_y.y + 1.001;
-                }
-                velocity.0.y = 0.;
-                stopped = true;
-                break;
-            }
-        }
-        if !stopped {
-            transform.translation.y += applied_velocity.y;
-        }
-        // z
-        let zpos: f32 = if applied_velocity.z > 0. { aabb.0.z + transform.translation.z } else { transform.translation.z }; 
-        let mut stopped = false;
-        for z in extent(zpos, applied_velocity.z).into_iter().skip(1) {
-            let pos_z = Vec3 {x: transform.translation.x, y: transform.translation.y, z: z as f32 };
-            if blocks_perp_z(pos_z, *realm, aabb).any(|pos| !blocks.get_block(pos).is_traversable()) {
-                // there's a collision in this direction, stop at the block limit
-                if applied_velocity.z > 0. { 
-                    transform.translation.z = pos_z.z - aabb.0.z - 0.001;
-                } else { 
-                    transform.translation.z = pos_z.z + 1.001;
-                }
-                velocity.0.z = 0.;
-                stopped = true;
-                break;
+        let applied_velocity = velocity.0*time.delta_seconds();
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            let delta = applied_velocity*axis;
+            match blocks.sweep_aabb(*realm, aabb.0, transform.translation, delta) {
+                Some(hit) => {
+                    // there's a collision in this direction, stop right before the block
+                    transform.translation += delta*hit.t + hit.normal*COLLISION_GAP;
+                    velocity.0 *= Vec3::ONE - axis;
+                },
+                None => transform.translation += delta,
             }
         }
-        if !stopped {
-            transform.translation.z += applied_velocity.z;
-        }
     }
 }
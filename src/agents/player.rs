@@ -1,5 +1,5 @@
 use std::time::Duration;
-use crate::{agents::{Gravity, Heading, Jumping, Velocity, AABB}, items::{new_inventory, InventoryTrait, Item, Stack}, sounds::{on_item_get, BlockSoundCD, FootstepCD}, ui::{ControllingPlayer, ItemHolder}, world::RenderDistance, Block};
+use crate::{agents::{Gravity, Heading, Jumping, Velocity, AABB}, items::{new_inventory, InventoryTrait, Item, Stack}, save::WorldSave, sounds::{on_item_get, BlockSoundCD, FootstepCD}, ui::{ControllingPlayer, ItemHolder}, world::RenderDistance, Block};
 use crate::world::{Realm, BlockRayCastHit};
 use bevy::{
     math::Vec3,
@@ -65,6 +65,8 @@ impl From<Dir> for Vec3 {
 pub enum Action {
     Hit,
     Modify,
+    Undo,
+    Redo,
 }
 
 #[derive(Actionlike, PartialEq, Eq, Clone, Copy, Debug, Hash, Reflect)]
@@ -72,18 +74,24 @@ pub enum DevCommand {
     ToggleFly,
 }
 
-pub fn spawn_player(mut commands: Commands) {    
-    let realm = Realm::Overworld;
+pub fn spawn_player(mut commands: Commands, mut world_save: ResMut<WorldSave>) {    
+    let mut inventory = new_inventory::<HOTBAR_SLOTS>();
+    let (realm, translation, rd) = if let Some(player_save) = world_save.meta.player.as_mut() {
+        for (slot, stack) in inventory.iter_mut().zip(player_save.inventory.drain(..)) {
+            *slot = stack;
+        }
+        (player_save.realm, Vec3::from_array(player_save.translation), RenderDistance(player_save.render_distance))
+    } else {
+        inventory.try_add(Stack::Some(Item::Block(Block::Smelter), 1));
+        inventory.try_add(Stack::Some(Item::Coal, 20));
+        inventory.try_add(Stack::Some(Item::IronOre, 50));
+        // Render distance nerfed from 64 to 16 while we don't have instancing
+        (Realm::Overworld, SPAWN, RenderDistance(16))
+    };
     let spatial_bundle = SpatialBundle {
-        transform: Transform {translation: SPAWN, ..default()},
+        transform: Transform {translation, ..default()},
         ..default()
     };
-    let mut inventory = new_inventory::<HOTBAR_SLOTS>();
-    inventory.try_add(Stack::Some(Item::Block(Block::Smelter), 1));
-    inventory.try_add(Stack::Some(Item::Coal, 20));
-    inventory.try_add(Stack::Some(Item::IronOre, 50));
-    // Render distance nerfed from 64 to 16 while we don't have instancing
-    let rd = RenderDistance(16);
     commands
         .spawn((
             spatial_bundle,
@@ -123,6 +131,8 @@ pub fn spawn_player(mut commands: Commands) {
                 (Action::Hit, MouseButton::Left),
                 (Action::Modify, MouseButton::Right),
             ])
+            .with(Action::Undo, ButtonlikeChord::modified(ModifierKey::Control, KeyCode::KeyZ))
+            .with(Action::Redo, ButtonlikeChord::modified(ModifierKey::Control, KeyCode::KeyY))
         })
         .insert(InputManagerBundle::<DevCommand> {
             action_state: ActionState::default(),
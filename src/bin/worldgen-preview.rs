@@ -0,0 +1,170 @@
+//! Renders the Overworld generation of an area to PNG maps, without a window or GPU:
+//! `cargo run --bin worldgen-preview -- --seed 42 --area -4,-4,3,3 --out worldgen_preview`
+//! The area is given in columns (min x, min z, max x, max z), images have 1 pixel per block with x to the right and z down.
+use std::{collections::HashMap, fs, path::Path, str::FromStr};
+use image::{Rgb, RgbImage};
+use itertools::iproduct;
+use riverbed::{
+    gen::{Earth, EarthMaps, EarthSettings, Tree, EARTH_SETTINGS_FILE},
+    world::{Biomes, BlockPos, BlockPos2d, ColPos, Realm, VoxelWorld, BIOMES_FILE, CHUNK_S1, CHUNK_S1I, MAX_GEN_HEIGHT, WATER_H},
+    arg_value, Block, BlockFamily,
+};
+
+const SOILS_COLOR: &str = "assets/gen/soils_color.csv";
+
+fn parse_area(area: &str) -> Option<[i32; 4]> {
+    let coords: Vec<i32> = area.split(',').map(|coord| coord.trim().parse().ok()).collect::<Option<_>>()?;
+    coords.try_into().ok()
+}
+
+/// Colors of soils_color.csv, written as 3 hex digits
+fn block_colors() -> HashMap<Block, Rgb<u8>> {
+    let content = fs::read_to_string(SOILS_COLOR).unwrap();
+    content.lines().skip(1).filter_map(|line| {
+        let (id, color) = line.split_once(',')?;
+        let block = Block::from_str(id.trim()).ok()?;
+        let digits: Vec<u8> = color.trim().trim_start_matches('#').chars()
+            .map(|digit| digit.to_digit(16).map(|value| value as u8 * 17))
+            .collect::<Option<_>>()?;
+        let [r, g, b] = digits.try_into().ok()?;
+        Some((block, Rgb([r, g, b])))
+    }).collect()
+}
+
+fn block_color(colors: &HashMap<Block, Rgb<u8>>, block: Block) -> Rgb<u8> {
+    if let Some(color) = colors.get(&block) {
+        return *color;
+    }
+    match block.families().first() {
+        Some(BlockFamily::Leaves) => Rgb([0x36, 0x62, 0x22]),
+        Some(BlockFamily::Log) | Some(BlockFamily::Planks) => Rgb([0x94, 0x61, 0x33]),
+        Some(BlockFamily::Ore) => Rgb([0xb8, 0x6f, 0x50]),
+        Some(BlockFamily::Stone) => Rgb([0x77, 0x77, 0x77]),
+        _ => Rgb([0x80, 0x80, 0x80]),
+    }
+}
+
+fn gray(value: f32) -> Rgb<u8> {
+    let value = (value.clamp(0., 1.) * 255.) as u8;
+    Rgb([value, value, value])
+}
+
+/// From blue for 0 to red for 1
+fn heat(value: f32) -> Rgb<u8> {
+    let value = value.clamp(0., 1.);
+    Rgb([(value * 255.) as u8, 64, ((1. - value) * 255.) as u8])
+}
+
+fn tree_color(tree: Tree) -> Rgb<u8> {
+    // a stable color per tree kind
+    let hash = format!("{:?}", tree).bytes().fold(7u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as u32));
+    Rgb([(hash >> 16) as u8 | 0x40, (hash >> 8) as u8 | 0x40, hash as u8 | 0x40])
+}
+
+struct Maps {
+    images: Vec<(&'static str, RgbImage)>,
+}
+
+impl Maps {
+    const NAMES: [&'static str; 9] = ["height", "surface", "biomes", "caves", "temperature", "humidity", "ph", "rifts", "trees"];
+
+    fn new(width: u32, height: u32) -> Self {
+        Maps { images: Maps::NAMES.into_iter().map(|name| (name, RgbImage::new(width, height))).collect() }
+    }
+
+    fn get(&mut self, name: &str) -> &mut RgbImage {
+        &mut self.images.iter_mut().find(|(image_name, _)| *image_name == name).unwrap().1
+    }
+
+    fn save(&self, dir: &Path) {
+        fs::create_dir_all(dir).unwrap();
+        for (name, image) in &self.images {
+            let path = dir.join(format!("{name}.png"));
+            image.save(&path).unwrap();
+            println!("wrote {}", path.display());
+        }
+    }
+}
+
+fn draw_noise_maps(maps: &mut Maps, earth: &Earth, earth_maps: &EarthMaps, (px, pz): (u32, u32)) {
+    for (dx, dz) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1) {
+        let (x, z) = (px + dx as u32, pz + dz as u32);
+        let ground = earth_maps.ground((dx, dz));
+        let height_color = if earth_maps.height((dx, dz)) <= WATER_H {
+            Rgb([0, 0, (ground as f32 / WATER_H as f32 * 200.) as u8 + 55])
+        } else {
+            gray(ground as f32 / MAX_GEN_HEIGHT as f32)
+        };
+        maps.get("height").put_pixel(x, z, height_color);
+        maps.get("biomes").put_pixel(x, z, Rgb(earth.biomes().get(earth_maps.biome((dx, dz))).grass_tint));
+        maps.get("temperature").put_pixel(x, z, heat(earth_maps.temperature[[dx, dz]]));
+        maps.get("humidity").put_pixel(x, z, heat(1. - earth_maps.humidity[[dx, dz]]));
+        maps.get("ph").put_pixel(x, z, heat(earth_maps.ph[[dx, dz]]));
+        maps.get("rifts").put_pixel(x, z, gray(earth_maps.rifts[[dx, dz]]));
+        maps.get("trees").put_pixel(x, z, gray(earth_maps.forests[[dx, dz]] * 0.5));
+    }
+}
+
+/// How much of the ground under pos was carved by caves, in blue where they're flooded
+fn cave_color(world: &VoxelWorld, pos: BlockPos2d, ground: i32, depth: i32) -> Rgb<u8> {
+    let (mut air, mut water) = (0, 0);
+    for y in (ground - depth)..=ground {
+        match world.get_block_safe(BlockPos { x: pos.x, y, z: pos.z, realm: pos.realm }) {
+            Block::Air => air += 1,
+            Block::SeaBlock => water += 1,
+            _ => {}
+        }
+    }
+    let carved = ((air + water) as f32 / depth as f32 * 4.).min(1.);
+    if water > 0 {
+        Rgb([0, 0, (55. + carved * 200.) as u8])
+    } else {
+        gray(carved)
+    }
+}
+
+fn main() {
+    let seed: u32 = arg_value("--seed").and_then(|seed| seed.parse().ok()).unwrap_or(0);
+    let [x0, z0, x1, z1] = arg_value("--area").and_then(|area| parse_area(&area)).unwrap_or([-4, -4, 3, 3]);
+    let out = arg_value("--out").unwrap_or("worldgen_preview".to_string());
+    let settings = EarthSettings::from_file(&arg_value("--settings").unwrap_or(EARTH_SETTINGS_FILE.to_string()));
+    let depth = settings.caves.depth;
+    let earth = Earth::new(seed, settings, Biomes::from_file(BIOMES_FILE));
+    let world = VoxelWorld::new();
+    let cols: Vec<ColPos> = iproduct!(x0..=x1, z0..=z1).map(|(x, z)| ColPos { x, z, realm: Realm::Overworld }).collect();
+    let (width, height) = (((x1 - x0 + 1) * CHUNK_S1I) as u32, ((z1 - z0 + 1) * CHUNK_S1I) as u32);
+    let mut maps = Maps::new(width, height);
+    let mut trees = Vec::new();
+    for col in cols.iter() {
+        let corner = (((col.x - x0) * CHUNK_S1I) as u32, ((col.z - z0) * CHUNK_S1I) as u32);
+        let earth_maps = earth.maps(*col);
+        draw_noise_maps(&mut maps, &earth, &earth_maps, corner);
+        trees.extend(earth.tree_spots(*col, &earth_maps));
+        earth.gen(&world, *col);
+        for (dx, dz) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1) {
+            let pos = BlockPos2d { x: col.x * CHUNK_S1I + dx as i32, z: col.z * CHUNK_S1I + dz as i32, realm: Realm::Overworld };
+            let color = cave_color(&world, pos, earth_maps.ground((dx, dz)), depth);
+            maps.get("caves").put_pixel(corner.0 + dx as u32, corner.1 + dz as u32, color);
+        }
+    }
+    // the surface is read once everything is generated because trees can grow over the neighbouring columns
+    let colors = block_colors();
+    for col in cols.iter() {
+        world.compute_heightmaps(*col);
+    }
+    for (x, z) in iproduct!(0..width, 0..height) {
+        let pos = BlockPos2d { x: x0 * CHUNK_S1I + x as i32, z: z0 * CHUNK_S1I + z as i32, realm: Realm::Overworld };
+        let (block, _) = world.top_block(pos);
+        maps.get("surface").put_pixel(x, z, block_color(&colors, block));
+    }
+    for (pos, tree, _) in trees {
+        let (x, z) = (pos.x - x0 * CHUNK_S1I, pos.z - z0 * CHUNK_S1I);
+        for (dx, dz) in iproduct!(-1..=1, -1..=1) {
+            let (x, z) = (x + dx, z + dz);
+            if x >= 0 && z >= 0 && (x as u32) < width && (z as u32) < height {
+                maps.get("trees").put_pixel(x as u32, z as u32, tree_color(tree));
+            }
+        }
+    }
+    maps.save(Path::new(&out));
+}
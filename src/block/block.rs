@@ -46,6 +46,17 @@ impl Block {
         self.families().contains(&BlockFamily::Leaves)
     }
 
+    /// Block light emitted by the block, from 0 to 15
+    pub fn light_emission(&self) -> u8 {
+        match self {
+            Block::CampfireOn | Block::Glowstone => 15,
+            Block::SmelterOn => 14,
+            Block::KilnOn => 13,
+            Block::Magma => 12,
+            _ => 0
+        }
+    }
+
     pub fn is_fertile_soil(&self) -> bool {
         match self {
             Block::GrassBlock | Block::Podzol | Block::Snow
@@ -54,3 +65,19 @@ impl Block {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use riverbed_block_def::BlockIds;
+    use strum::IntoEnumIterator;
+    use crate::Block;
+
+    #[test]
+    fn test_block_ids_are_checked_in() {
+        let block_ids = BlockIds::from_ron(&fs::read_to_string("assets/data/block_ids.ron").unwrap()).unwrap();
+        for block in Block::iter() {
+            assert_eq!(block_ids.get(&block.to_string()), Some(block.id()), "{block} has no id in block_ids.ron, see the build warning");
+        }
+    }
+}
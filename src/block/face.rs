@@ -36,6 +36,22 @@ impl Face {
         }
     }
 
+    pub fn opposite(&self) -> Face {
+        match self {
+            Self::Left => Self::Right,
+            Self::Down => Self::Up,
+            Self::Back => Self::Front,
+            Self::Right => Self::Left,
+            Self::Up => Self::Down,
+            Self::Front => Self::Back,
+        }
+    }
+
+    /// True if the face normal points towards the positive side of its axis
+    pub fn is_positive(&self) -> bool {
+        matches!(self, Self::Right | Self::Up | Self::Front)
+    }
+
     pub fn specifiers(&self) -> &[FaceSpecifier] {
         match self {
             Self::Left => &LEFT_SPECIFIER,
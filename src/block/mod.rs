@@ -1,4 +1,6 @@
 mod face;
 mod block;
+mod state;
 pub use face::*;
 pub use block::*;
+pub use state::*;
@@ -0,0 +1,90 @@
+use bevy::prelude::Vec3;
+use crate::{Block, BlockStateKind};
+use super::Face;
+
+/// Per-voxel state of a block (orientation, growth stage, water level...),
+/// its meaning depends on the BlockStateKind of the block, 0 is the default state
+pub type BlockState = u8;
+
+// Horizontal faces in clockwise order seen from above, indexed by Facing states
+const HORIZONTAL_FACES: [Face; 4] = [Face::Front, Face::Right, Face::Back, Face::Left];
+
+impl BlockStateKind {
+    pub fn count(&self) -> u8 {
+        match self {
+            BlockStateKind::Axis => 3,
+            BlockStateKind::Facing => 4,
+            BlockStateKind::Growth(stages) => *stages,
+            BlockStateKind::Level(levels) => *levels,
+        }
+    }
+}
+
+impl Block {
+    /// Number of states the block can be in, 1 for blocks without state
+    pub fn state_count(&self) -> u8 {
+        self.state_kind().map_or(1, |kind| kind.count())
+    }
+
+    /// The state a block gets when placed against a face with normal `normal`
+    pub fn placed_state(&self, normal: Vec3) -> BlockState {
+        match self.state_kind() {
+            Some(BlockStateKind::Axis) if normal.x != 0. => 1,
+            Some(BlockStateKind::Axis) if normal.z != 0. => 2,
+            Some(BlockStateKind::Facing) => match (normal.x as i32, normal.z as i32) {
+                (1, _) => 1,
+                (_, -1) => 2,
+                (-1, _) => 3,
+                _ => 0,
+            },
+            _ => 0,
+        }
+    }
+
+    /// The face of the block textures to display on `face` of a block in `state`,
+    /// textures are defined for the default state (Y axis, facing front)
+    pub fn textured_face(&self, state: BlockState, face: Face) -> Face {
+        match (self.state_kind(), state) {
+            // lying along X, the ends of the block are on the left and right
+            (Some(BlockStateKind::Axis), 1) => match face {
+                Face::Right => Face::Up,
+                Face::Left => Face::Down,
+                Face::Up => Face::Left,
+                Face::Down => Face::Right,
+                _ => face,
+            },
+            // lying along Z, the ends of the block are on the front and back
+            (Some(BlockStateKind::Axis), 2) => match face {
+                Face::Front => Face::Up,
+                Face::Back => Face::Down,
+                Face::Up => Face::Back,
+                Face::Down => Face::Front,
+                _ => face,
+            },
+            (Some(BlockStateKind::Facing), facing) => match HORIZONTAL_FACES.iter().position(|f| *f == face) {
+                Some(i) => HORIZONTAL_FACES[(i + 4 - facing as usize % 4) % 4],
+                None => face,
+            },
+            _ => face,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Vec3;
+    use crate::{block::Face, Block};
+
+    #[test]
+    fn test_textured_face() {
+        // a log along X shows its ends on the left and right
+        let state = Block::OakLog.placed_state(Vec3::X);
+        assert_eq!(Block::OakLog.textured_face(state, Face::Right), Face::Up);
+        assert_eq!(Block::OakLog.textured_face(state, Face::Front), Face::Front);
+        // a furnace placed against the right face of a block shows its front on the right
+        let state = Block::Kiln.placed_state(Vec3::X);
+        assert_eq!(Block::Kiln.textured_face(state, Face::Right), Face::Front);
+        assert_eq!(Block::Kiln.textured_face(state, Face::Up), Face::Up);
+        assert_eq!(Block::Granite.textured_face(3, Face::Left), Face::Left);
+    }
+}
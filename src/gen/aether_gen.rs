@@ -0,0 +1,47 @@
+use crate::Block;
+use crate::world::{ColPos, VoxelWorld, CHUNK_S1};
+use bevy::prelude::info_span;
+use itertools::iproduct;
+use noise_algebra::NoiseSource;
+use super::earth_gen::pos_to_range;
+
+/// The floating islands of the Aether float around this height
+pub const ISLANDS_H: i32 = 200;
+// islands are where the island noise is above this threshold, there's only void elsewhere
+const ISLAND_T: f32 = 0.75;
+
+pub struct Aether {
+    seed: i32,
+}
+
+impl Aether {
+    pub fn new(seed: u32) -> Self {
+        Aether { seed: seed as i32 }
+    }
+
+    pub fn gen(&self, world: &VoxelWorld, col: ColPos) {
+        let range = pos_to_range(col);
+        let gen_span = info_span!("noise gen", name = "noise gen").entered();
+        let mut n = NoiseSource::new(range, self.seed, 1);
+        let islands = (n.simplex(0.5) + n.simplex(3.) * 0.4 + n.simplex(15.) * 0.1).normalize();
+        let heights = (n.simplex(0.2) + n.simplex(2.) * 0.2).normalize();
+        gen_span.exit();
+        let fill_span = info_span!("chunk filling", name = "chunk filling").entered();
+        for (dx, dz) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1) {
+            let island = islands[[dx, dz]];
+            if island < ISLAND_T {
+                continue;
+            }
+            // 0 on the shore of the island, 1 at its heart
+            let inland = (island - ISLAND_T) / (1. - ISLAND_T);
+            let center = ISLANDS_H + ((heights[[dx, dz]] - 0.5) * 120.) as i32;
+            // islands are flat on top and hang down like upside-down mountains
+            let top = center + (inland * 8.) as i32;
+            let depth = 2 + (inland.sqrt() * 48.) as usize;
+            world.set_yrange(col, (dx, dz), top - 3, depth, Block::Limestone);
+            world.set_yrange(col, (dx, dz), top - 1, 2, Block::Dirt);
+            world.set_yrange(col, (dx, dz), top, 1, Block::GrassBlock);
+        }
+        fill_span.exit();
+    }
+}
@@ -0,0 +1,104 @@
+use crate::Block;
+use crate::world::{chunked, ChunkPos, ChunkedPos, ColPos, VoxelWorld, CHUNK_S1, CHUNK_S1I, WATER_H};
+use bevy::prelude::info_span;
+use itertools::iproduct;
+use noise_algebra::{NoiseSource, Signal3d};
+use super::{earth_gen::EarthMaps, earth_settings::Caves};
+
+/// The cave noises of a chunk, indexed with [[dx, dy, dz]], values are between 0 and 1
+struct CaveNoise {
+    cheese: Signal3d,
+    spaghetti: (Signal3d, Signal3d),
+    aquifers: Signal3d,
+}
+
+impl CaveNoise {
+    /// The noises of the chunk sized cube starting at this corner
+    fn new(settings: &Caves, seed: i32, (x, y, z): (i32, i32, i32)) -> Self {
+        // the noise only lines up with the array when the 3 ranges have the same length,
+        // they're given as z, y, x so the array is indexed with [[dx, dy, dz]] like the 2D maps
+        let range = [z..=(z + CHUNK_S1I - 1), y..=(y + CHUNK_S1I - 1), x..=(x + CHUNK_S1I - 1)];
+        let mut n = NoiseSource::new(range, seed, 1);
+        CaveNoise {
+            cheese: settings.cheese.simplex3d(&mut n).normalize(),
+            spaghetti: (
+                settings.spaghetti.0.simplex3d(&mut n).normalize(),
+                settings.spaghetti.1.simplex3d(&mut n).normalize(),
+            ),
+            aquifers: settings.aquifers.simplex3d(&mut n).normalize(),
+        }
+    }
+
+    /// depth is how far under the ground pos is, caves shrink close to the surface
+    fn is_cave(&self, settings: &Caves, (dx, dy, dz): ChunkedPos, depth: i32) -> bool {
+        let pos = [dx, dy, dz];
+        let fade = (depth as f32 / settings.surface_fade as f32).min(1.);
+        let cheese_threshold = settings.opening_threshold + (settings.cheese_threshold - settings.opening_threshold) * fade;
+        let spaghetti_width = settings.spaghetti_width * fade;
+        self.cheese[pos] > cheese_threshold || (
+            (self.spaghetti.0[pos] - 0.5).abs() < spaghetti_width
+            && (self.spaghetti.1[pos] - 0.5).abs() < spaghetti_width
+        )
+    }
+
+    /// The block that fills the cave at pos
+    fn fill(&self, settings: &Caves, (dx, dy, dz): ChunkedPos, y: i32) -> Block {
+        if y <= WATER_H && self.aquifers[[dx, dy, dz]] > settings.aquifer_threshold {
+            Block::SeaBlock
+        } else {
+            Block::Air
+        }
+    }
+}
+
+/// Carves the caves of a filled column, they only depend on the seed and the position
+/// so they carry on across column borders
+pub fn carve_caves(world: &VoxelWorld, col: ColPos, maps: &EarthMaps, settings: &Caves, seed: i32) {
+    let _span = info_span!("cave carving", name = "cave carving").entered();
+    let grounds = iproduct!(0..CHUNK_S1, 0..CHUNK_S1).map(|pos| maps.ground(pos));
+    let (min_cy, _) = chunked(grounds.clone().min().unwrap() - settings.depth);
+    let (max_cy, _) = chunked(grounds.max().unwrap());
+    for cy in min_cy..=max_cy {
+        let chunk_pos = ChunkPos { x: col.x, y: cy, z: col.z, realm: col.realm };
+        let caves = CaveNoise::new(settings, seed, (col.x * CHUNK_S1I, cy * CHUNK_S1I, col.z * CHUNK_S1I));
+        let Some(mut chunk) = world.chunks.get_mut(&chunk_pos) else {
+            continue;
+        };
+        for (dx, dz) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1) {
+            let ground = maps.ground((dx, dz));
+            let ceiling = if maps.height((dx, dz)) < WATER_H { ground - settings.sea_roof } else { ground };
+            for dy in 0..CHUNK_S1 {
+                let y = cy * CHUNK_S1I + dy as i32;
+                if y < ground - settings.depth || y > ceiling {
+                    continue;
+                }
+                let pos = (dx, dy, dz);
+                if !chunk.get(pos).is_targetable() || !caves.is_cave(settings, pos, ground - y) {
+                    continue;
+                }
+                chunk.set(pos, caves.fill(settings, pos, y));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::iproduct;
+    use crate::world::CHUNK_S1;
+    use super::{CaveNoise, Caves};
+
+    #[test]
+    fn test_caves_line_up() {
+        let settings = Caves::default();
+        let caves = CaveNoise::new(&settings, 42, (0, 0, 0));
+        // a cube shifted by half a chunk on each axis must have the same caves where they overlap
+        let half = CHUNK_S1 / 2;
+        let shifted = CaveNoise::new(&settings, 42, (half as i32, -(half as i32), half as i32));
+        for (dx, dy, dz) in iproduct!(half..CHUNK_S1, 0..half, half..CHUNK_S1) {
+            let shifted_pos = (dx - half, dy + half, dz - half);
+            assert_eq!(caves.is_cave(&settings, (dx, dy, dz), 10), shifted.is_cave(&settings, shifted_pos, 10));
+            assert_eq!(caves.fill(&settings, (dx, dy, dz), 0), shifted.fill(&settings, shifted_pos, 0));
+        }
+    }
+}
@@ -1,165 +1,187 @@
-use crate::{Block, gen::Soils};
+use crate::Block;
 use crate::world::{
-    BlockPos, BlockPos2d, ColPos, VoxelWorld, CHUNK_S1, CHUNK_S1I, MAX_GEN_HEIGHT, WATER_H,
+    biome_index, BiomeId, Biomes, BlockPos, BlockPos2d, ColBiomes, ColPos, ColedPos, VoxelWorld, CHUNK_S1, CHUNK_S1I,
+    MAX_GEN_HEIGHT, WATER_H,
 };
 use riverbed_closest::{points, ranges, ClosestTrait};
 use bevy::prelude::info_span;
 use itertools::iproduct;
-use noise_algebra::NoiseSource;
-use std::{collections::HashMap, ops::RangeInclusive};
+use noise_algebra::{NoiseSource, Signal2d};
+use std::ops::RangeInclusive;
 
-use super::tree::Trees;
-pub const CONT_R: f32 = (WATER_H + 2) as f32 / MAX_GEN_HEIGHT as f32;
-pub const CONT_COMPL: f32 = 1. - CONT_R;
+use super::{caves::carve_caves, earth_settings::EarthSettings, tree::{Tree, Trees}, Soils};
+// each column has a tree spot in each of these 8x8 squares, trees grow there if the conditions are right
+const TREE_SPOTS: [ColedPos; 14] = [
+    (0, 0),
+    (15, 0),
+    (31, 0),
+    (46, 0),
+    (8, 15),
+    (24, 15),
+    (40, 15),
+    (0, 31),
+    (15, 31),
+    (31, 31),
+    (46, 31),
+    (8, 46),
+    (24, 46),
+    (40, 46),
+];
 
 pub struct Earth {
+    biomes: Biomes,
+    /// Soils of the biomes that don't have their own
     soils: Soils,
-    trees: Trees,
+    /// The trees of each biome, indexed by BiomeId
+    trees: Vec<Trees>,
     seed: i32,
-    config: HashMap<String, f32>,
+    settings: EarthSettings,
 }
 
-fn pos_to_range(pos: ColPos) -> [RangeInclusive<i32>; 2] {
+/// The noises that shape a column, indexed with [[dx, dz]], values are between 0 and 1
+pub struct EarthMaps {
+    /// Height of the ground as a ratio of MAX_GEN_HEIGHT, before rifts are carved in it
+    pub heights: Signal2d,
+    pub temperature: Signal2d,
+    pub humidity: Signal2d,
+    pub ph: Signal2d,
+    pub rocks: Signal2d,
+    /// Depth of the rifts as a ratio of their max depth
+    pub rifts: Signal2d,
+    pub forests: Signal2d,
+    /// Biome of each position, indexed with biome_index
+    pub biomes: Vec<BiomeId>,
+    /// One per ore band of the settings
+    pub ores: Vec<Signal2d>,
+    rift_depth: f32,
+}
+
+impl EarthMaps {
+    pub fn height(&self, (dx, dz): ColedPos) -> i32 {
+        (self.heights[[dx, dz]] * MAX_GEN_HEIGHT as f32) as i32
+    }
+
+    pub fn rift(&self, (dx, dz): ColedPos) -> i32 {
+        (self.rifts[[dx, dz]] * self.rift_depth) as i32
+    }
+
+    /// Height of the ground once the rifts are carved
+    pub fn ground(&self, pos: ColedPos) -> i32 {
+        (self.height(pos) - self.rift(pos)).max(1)
+    }
+
+    pub fn biome(&self, pos: ColedPos) -> BiomeId {
+        self.biomes[biome_index(pos)]
+    }
+}
+
+pub(super) fn pos_to_range(pos: ColPos) -> [RangeInclusive<i32>; 2] {
     let x = pos.z * CHUNK_S1I;
     let y = pos.x * CHUNK_S1I;
     [x..=(x + CHUNK_S1I - 1), y..=(y + CHUNK_S1I - 1)]
 }
 
 impl Earth {
-    pub fn new(seed: u32, config: HashMap<String, f32>) -> Self {
+    pub fn new(seed: u32, settings: EarthSettings, biomes: Biomes) -> Self {
+        let trees: Trees = ranges::from_csv("assets/gen/trees_condition.csv").unwrap();
+        let biome_trees = biomes.iter().map(|(_, biome)|
+            trees.iter().filter(|(_, tree)| biome.trees.contains(tree)).cloned().collect()
+        ).collect();
         Earth {
+            biomes,
             soils: ranges::from_csv("assets/gen/soils_condition.csv").unwrap(),
-            trees: ranges::from_csv("assets/gen/trees_condition.csv").unwrap(),
+            trees: biome_trees,
             seed: seed as i32,
-            config,
+            settings,
         }
     }
 
-    pub fn gen(&self, world: &VoxelWorld, col: ColPos) {
-        //let landratio = self.config.get("land_ratio").copied().unwrap_or(0.4);
+    pub fn maps(&self, col: ColPos) -> EarthMaps {
+        let EarthSettings { continents, mountains, rocks, rifts, temperature, humidity, acidity, forests, ores, .. } = &self.settings;
         let range = pos_to_range(col);
-        let gen_span = info_span!("noise gen", name = "noise gen").entered();
+        let _span = info_span!("noise gen", name = "noise gen").entered();
+        // the noises are sampled in a fixed order since each one uses the next seed
         let mut n = NoiseSource::new(range, self.seed, 1);
-        let continentalness = n.simplex(0.2);
-        let cont =
-            (n.simplex(1.) * 0.3 + n.simplex(5.) * 0.1 + n.simplex(20.) * 0.05 + &continentalness)
-                .normalize()
-                .cap(CONT_R);
-        let rocks =
-            !(n.simplex(0.5) + n.simplex(4.) * 0.2 + n.simplex(16.) * 0.1 + n.simplex(80.) * 0.05)
-                .normalize()
-                .cap(0.08);
-        let mountain_control = n.ridge(0.2);
-        let mountain = (n.simplex(2.) + n.simplex(10.) * 0.1 + n.simplex(50.) * 0.005).normalize()
-            * mountain_control.powi(2);
-        let ts = (n.simplex(0.05)
-            + n.simplex(0.4) * 0.1
-            + n.simplex(8.) * 0.05
-            + n.simplex(100.) * 0.01)
-            .normalize();
-        let hs = (n.simplex(0.1)
-            + !continentalness * 0.5
-            + n.simplex(10.) * 0.1
-            + n.simplex(60.) * 0.04)
-            .normalize();
-        let ph = (n.simplex(1.) + n.simplex(4.) * 0.2 + n.simplex(40.) * 0.1).normalize();
-        let rift_control = (n.ridge(1.) + n.ridge(20.) * 0.05).normalize().powi(2);
-        let rift = ((n.simplex(0.3) + n.simplex(1.) * 0.2 + !ph.clone() * 0.5).normalize()
-            * rift_control)
-            .threshold(0.9);
-        let trees =
-            (n.simplex(1.) + &hs * 0.3 + n.simplex(5.) * 0.4 + n.simplex(20.) * 0.2).normalize();
-        let iron = (n.simplex(8.) + n.simplex(16.) * 0.1).normalize();
-        let ys = cont + &mountain * CONT_COMPL + &rocks;
-        // convert y to convenient values
-        let ys = ys.map(|y| (y * MAX_GEN_HEIGHT as f32) as i32);
-        let rift = rift.map(|r| (r * (MAX_GEN_HEIGHT / 2) as f32) as i32);
-        gen_span.exit();
-        let fill_span = info_span!("chunk filling", name = "chunk filling").entered();
-        for (dx, dz) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1) {
-            let (base_y, t, h, rocks, rift, iron) = (
-                ys[[dx, dz]],
-                ts[[dx, dz]],
-                hs[[dx, dz]],
-                rocks[[dx, dz]],
-                rift[[dx, dz]],
-                iron[[dx, dz]],
-            );
-            let y = (base_y - rift).max(1);
-            let block = if rocks > 0.001 || rift > 6 {
-                Block::Cobblestone
-            } else if base_y <= WATER_H {
-                Block::Sand
-            } else {
-                let (block, value) = self.soils.closest([t, h]);
-                if value < 0. {
-                    Block::Dirt
-                } else {
-                    *block
-                }
-            };
-            world.set_yrange(col, (dx, dz), y, 4, block);
-            world.set_yrange(col, (dx, dz), y - 4, 2, Block::Cobblestone);
-            world.set_yrange(col, (dx, dz), y - 6, 24, Block::Granite);
-            if rift > 18 && rift < 24 && iron > 0.9 {
-                let height = if iron > 0.97 {
-                    4
-                } else if iron > 0.94 {
-                    3
-                } else if iron > 0.91 {
-                    2
-                } else {
-                    1
-                };
-                world.set_yrange(col, (dx, dz), y, height, Block::IronOre)
-            }
-            let water_height = WATER_H - base_y;
-            if water_height > 0 {
-                world.set_yrange(
-                    col,
-                    (dx, dz),
-                    WATER_H,
-                    (water_height + rift) as usize,
-                    Block::SeaBlock,
-                );
-            }
+        let continentalness = n.simplex(continents.scale);
+        let cont = (continents.detail.simplex(&mut n) + &continentalness)
+            .normalize()
+            .cap(continents.cap);
+        let rock_map = !rocks.shape.simplex(&mut n).normalize().cap(rocks.cap);
+        let mountain_control = n.ridge(mountains.control_scale);
+        let mountain = mountains.shape.simplex(&mut n).normalize() * mountain_control.powi(mountains.control_power);
+        let ts = temperature.simplex(&mut n).normalize();
+        let hs = (humidity.shape.simplex(&mut n) + !continentalness * humidity.ocean_weight).normalize();
+        let ph = acidity.simplex(&mut n).normalize();
+        let rift_control = rifts.control.ridge(&mut n).normalize().powi(2);
+        let rift = ((rifts.shape.simplex(&mut n) + !ph.clone() * rifts.acidity_weight).normalize() * rift_control)
+            .threshold(rifts.threshold);
+        let trees = (forests.shape.simplex(&mut n) + &hs * forests.humidity_weight).normalize();
+        let ore_veins = ores.iter().map(|ore| ore.shape.simplex(&mut n).normalize()).collect();
+        let biome_map = iproduct!(0..CHUNK_S1, 0..CHUNK_S1)
+            .map(|(dx, dz)| self.biomes.from_climate(ts[[dx, dz]], hs[[dx, dz]]))
+            .collect();
+        EarthMaps {
+            heights: cont + &mountain * (1. - continents.cap) + &rock_map,
+            temperature: ts,
+            humidity: hs,
+            ph,
+            rocks: rock_map,
+            rifts: rift,
+            forests: trees,
+            biomes: biome_map,
+            ores: ore_veins,
+            rift_depth: rifts.depth,
         }
-        fill_span.exit();
-        let tree_span = info_span!("tree gen", name = "tree gen").entered();
-        let tree_spots = [
-            (0, 0),
-            (15, 0),
-            (31, 0),
-            (46, 0),
-            (8, 15),
-            (24, 15),
-            (40, 15),
-            (0, 31),
-            (15, 31),
-            (31, 31),
-            (46, 31),
-            (8, 46),
-            (24, 46),
-            (40, 46),
-        ];
-        for spot in tree_spots {
+    }
+
+    pub fn biomes(&self) -> &Biomes {
+        &self.biomes
+    }
+
+    /// The block on top of the ground at pos and the block under it
+    pub fn surface(&self, maps: &EarthMaps, pos: ColedPos) -> (Block, Block) {
+        let (dx, dz) = pos;
+        if maps.rocks[[dx, dz]] > 0.001 || maps.rift(pos) > self.settings.rifts.rocky_depth {
+            (Block::Cobblestone, Block::Cobblestone)
+        } else if maps.height(pos) <= WATER_H {
+            (Block::Sand, Block::Sand)
+        } else {
+            let biome = self.biomes.get(maps.biome(pos));
+            let soil = biome.soil.unwrap_or_else(|| self.soil(maps, pos));
+            (soil, biome.filler.unwrap_or(soil))
+        }
+    }
+
+    /// The soil of soils_condition.csv whose climate ranges are the closest to the climate at pos
+    fn soil(&self, maps: &EarthMaps, (dx, dz): ColedPos) -> Block {
+        let (block, value) = self.soils.closest([maps.temperature[[dx, dz]], maps.humidity[[dx, dz]]]);
+        if value < 0. { Block::Dirt } else { *block }
+    }
+
+    /// The trees of the column with where they grow and their distance to the ideal conditions of the tree
+    pub fn tree_spots(&self, col: ColPos, maps: &EarthMaps) -> Vec<(BlockPos, Tree, f32)> {
+        let mut trees = Vec::new();
+        for spot in TREE_SPOTS {
             let rng = <BlockPos2d>::from((col, spot)).prng(self.seed);
             let dx = spot.0 + (rng & 0b111);
             let dz = spot.1 + ((rng >> 3) & 0b111);
-            if rift[[dx, dz]] > 0 {
+            if maps.rift((dx, dz)) > 0 {
                 continue;
             }
-            let tree = trees[[dx, dz]];
-            if tree < 0.5 {
+            if maps.forests[[dx, dz]] < self.settings.forests.threshold {
                 continue;
             }
             let h = (rng >> 5) & 0b11;
-            let y = ys[[dx, dz]];
+            let y = maps.height((dx, dz));
             if y > WATER_H {
-                let (tree, dist) = self.trees.closest([
-                    ts[[dx, dz]],
-                    hs[[dx, dz]],
-                    ph[[dx, dz]],
+                let biome_trees = &self.trees[maps.biome((dx, dz)).0 as usize];
+                if biome_trees.is_empty() {
+                    continue;
+                }
+                let (tree, dist) = biome_trees.closest([
+                    maps.temperature[[dx, dz]],
+                    maps.humidity[[dx, dz]],
+                    maps.ph[[dx, dz]],
                     y as f32 / MAX_GEN_HEIGHT as f32,
                 ]);
                 if dist >= 0. {
@@ -169,10 +191,95 @@ impl Earth {
                         z: col.z * CHUNK_S1I + dz as i32,
                         realm: col.realm,
                     };
-                    tree.grow(world, pos, self.seed, dist + h as f32 / 10.);
+                    trees.push((pos, *tree, dist + h as f32 / 10.));
                 }
             }
         }
+        trees
+    }
+
+    pub fn gen(&self, world: &VoxelWorld, col: ColPos) {
+        let maps = self.maps(col);
+        let fill_span = info_span!("chunk filling", name = "chunk filling").entered();
+        for (dx, dz) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1) {
+            let (base_y, rift) = (maps.height((dx, dz)), maps.rift((dx, dz)));
+            let y = maps.ground((dx, dz));
+            let (soil, filler) = self.surface(&maps, (dx, dz));
+            world.set_yrange(col, (dx, dz), y, 4, soil);
+            if filler != soil {
+                world.set_yrange(col, (dx, dz), y - 1, 3, filler);
+            }
+            world.set_yrange(col, (dx, dz), y - 4, 2, Block::Cobblestone);
+            world.set_yrange(col, (dx, dz), y - 6, 24, Block::Granite);
+            for (ore, veins) in self.settings.ores.iter().zip(&maps.ores) {
+                let vein = veins[[dx, dz]];
+                if rift > ore.rift_depth.0 && rift < ore.rift_depth.1 && vein > ore.threshold {
+                    let height = 1 + ore.thickening.iter().filter(|thickening| vein > **thickening).count();
+                    world.set_yrange(col, (dx, dz), y, height, ore.block)
+                }
+            }
+            let water_height = WATER_H - base_y;
+            if water_height > 0 {
+                world.set_yrange(
+                    col,
+                    (dx, dz),
+                    WATER_H,
+                    (water_height + rift) as usize,
+                    Block::SeaBlock,
+                );
+            }
+        }
+        fill_span.exit();
+        carve_caves(world, col, &maps, &self.settings.caves, self.seed);
+        world.set_col_biomes(col, ColBiomes::new(maps.biomes.clone()));
+        let tree_span = info_span!("tree gen", name = "tree gen").entered();
+        for (pos, tree, dist) in self.tree_spots(col, &maps) {
+            tree.grow(world, pos, self.seed, dist);
+        }
         tree_span.exit();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use itertools::iproduct;
+    use riverbed_closest::{ranges, ClosestTrait};
+    use crate::{gen::Soils, world::{pos2d::chunks_in_col, Biomes, Chunk, ColPos, Realm, VoxelWorld, BIOMES_FILE, CHUNK_S1, WATER_H}, Block};
+    use super::{Earth, EarthSettings};
+
+    #[test]
+    fn test_gen_chunks_roundtrip() {
+        let earth = Earth::new(42, EarthSettings::default(), Biomes::from_file(BIOMES_FILE));
+        let world = VoxelWorld::new();
+        for (x, z) in iproduct!(-1..=1, -1..=1) {
+            earth.gen(&world, ColPos { x, z, realm: Realm::Overworld });
+        }
+        let col = ColPos { x: 0, z: 0, realm: Realm::Overworld };
+        for chunk_pos in chunks_in_col(&col) {
+            let Some(chunk) = world.chunks.get(&chunk_pos) else {
+                continue;
+            };
+            let decoded = Chunk::decode(&chunk.encode()).unwrap();
+            for pos in iproduct!(0..CHUNK_S1, 0..CHUNK_S1, 0..CHUNK_S1) {
+                assert_eq!(chunk.get(pos), decoded.get(pos));
+            }
+        }
+    }
+
+    #[test]
+    fn test_biomes_without_soil_use_soils_csv() {
+        let earth = Earth::new(42, EarthSettings::default(), Biomes::from_file(BIOMES_FILE));
+        let soils: Soils = ranges::from_csv("assets/gen/soils_condition.csv").unwrap();
+        for (x, z) in iproduct!(-2..=2, -2..=2) {
+            let maps = earth.maps(ColPos { x, z, realm: Realm::Overworld });
+            for pos in iproduct!(0..CHUNK_S1, 0..CHUNK_S1) {
+                if maps.rocks[[pos.0, pos.1]] > 0.001 || maps.rift(pos) > 0 || maps.height(pos) <= WATER_H {
+                    continue;
+                }
+                let (block, value) = soils.closest([maps.temperature[[pos.0, pos.1]], maps.humidity[[pos.0, pos.1]]]);
+                let soil = if value < 0. { Block::Dirt } else { *block };
+                assert_eq!(earth.surface(&maps, pos), (soil, soil));
+            }
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::{fs, ops::{Add, Mul}};
+use noise_algebra::{NoiseSource, Signal2d, Signal3d};
+use serde::{Deserialize, Serialize};
+use crate::Block;
+use crate::world::{MAX_GEN_HEIGHT, WATER_H};
+
+pub const EARTH_SETTINGS_FILE: &str = "assets/gen/earth.json5";
+
+/// A sum of noises, each octave is a (frequency, amplitude) pair
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Octaves(pub Vec<(f32, f32)>);
+
+impl Octaves {
+    fn sample<N, S: Add<Output = S> + Mul<f32, Output = S>>(
+        &self, n: &mut N, noise: fn(&mut N, f32) -> S, constant: fn(&mut N, f32) -> S
+    ) -> S {
+        let mut octaves = self.0.iter();
+        let Some((freq, amp)) = octaves.next() else {
+            return constant(n, 0.);
+        };
+        let first = noise(n, *freq) * *amp;
+        octaves.fold(first, |sum, (freq, amp)| sum + noise(n, *freq) * *amp)
+    }
+
+    pub fn simplex(&self, n: &mut NoiseSource<2>) -> Signal2d {
+        self.sample(n, NoiseSource::<2>::simplex, NoiseSource::<2>::constant)
+    }
+
+    pub fn ridge(&self, n: &mut NoiseSource<2>) -> Signal2d {
+        self.sample(n, NoiseSource::<2>::ridge, NoiseSource::<2>::constant)
+    }
+
+    pub fn simplex3d(&self, n: &mut NoiseSource<3>) -> Signal3d {
+        self.sample(n, NoiseSource::<3>::simplex, NoiseSource::<3>::constant)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Continents {
+    /// Frequency of the noise that separates oceans from continents, humidity depends on it too
+    pub scale: f32,
+    /// Added to the continent noise to make the coasts less smooth
+    pub detail: Octaves,
+    /// Height of the continents as a ratio of MAX_GEN_HEIGHT, mountains make up the rest
+    pub cap: f32,
+}
+
+impl Default for Continents {
+    fn default() -> Self {
+        Continents {
+            scale: 0.2,
+            detail: Octaves(vec![(1., 0.3), (5., 0.1), (20., 0.05)]),
+            cap: (WATER_H + 2) as f32 / MAX_GEN_HEIGHT as f32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Mountains {
+    /// Frequency of the ridges along which mountains rise
+    pub control_scale: f32,
+    pub control_power: i32,
+    pub shape: Octaves,
+}
+
+impl Default for Mountains {
+    fn default() -> Self {
+        Mountains {
+            control_scale: 0.2,
+            control_power: 2,
+            shape: Octaves(vec![(2., 1.), (10., 0.1), (50., 0.005)]),
+        }
+    }
+}
+
+/// Rocky outcrops, made of cobblestone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rocks {
+    pub shape: Octaves,
+    /// The lower the cap, the fewer rocks
+    pub cap: f32,
+}
+
+impl Default for Rocks {
+    fn default() -> Self {
+        Rocks {
+            shape: Octaves(vec![(0.5, 1.), (4., 0.2), (16., 0.1), (80., 0.05)]),
+            cap: 0.08,
+        }
+    }
+}
+
+/// Deep cracks in the ground, they follow ridges and are more common where the soil is acid
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rifts {
+    /// Ridge noise along which the rifts open
+    pub control: Octaves,
+    pub shape: Octaves,
+    pub acidity_weight: f32,
+    /// Rifts open where the noise is above this
+    pub threshold: f32,
+    /// Depth of a rift at its deepest
+    pub depth: f32,
+    /// Rifts deeper than this have cobblestone walls
+    pub rocky_depth: i32,
+}
+
+impl Default for Rifts {
+    fn default() -> Self {
+        Rifts {
+            control: Octaves(vec![(1., 1.), (20., 0.05)]),
+            shape: Octaves(vec![(0.3, 1.), (1., 0.2)]),
+            acidity_weight: 0.5,
+            threshold: 0.9,
+            depth: (MAX_GEN_HEIGHT / 2) as f32,
+            rocky_depth: 6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Humidity {
+    pub shape: Octaves,
+    /// How much wetter it gets away from the continents
+    pub ocean_weight: f32,
+}
+
+impl Default for Humidity {
+    fn default() -> Self {
+        Humidity {
+            shape: Octaves(vec![(0.1, 1.), (10., 0.1), (60., 0.04)]),
+            ocean_weight: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Forests {
+    pub shape: Octaves,
+    /// How much denser forests are where it's humid
+    pub humidity_weight: f32,
+    /// Trees only grow where the forest noise is above this
+    pub threshold: f32,
+}
+
+impl Default for Forests {
+    fn default() -> Self {
+        Forests {
+            shape: Octaves(vec![(1., 1.), (5., 0.4), (20., 0.2)]),
+            humidity_weight: 0.3,
+            threshold: 0.5,
+        }
+    }
+}
+
+/// Caves carved in the ground with 3D noise, they can open on the surface as overhangs and arches
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Caves {
+    /// How deep under the ground caves go, the ground is about 30 blocks thick and caves
+    /// deeper than that would open on the void under it
+    pub depth: i32,
+    /// Big open caves are where this noise is above cheese_threshold
+    pub cheese: Octaves,
+    pub cheese_threshold: f32,
+    /// Tunnels follow the lines where both of these noises are close to 0.5
+    pub spaghetti: (Octaves, Octaves),
+    /// How far from 0.5 the spaghetti noises can be, the higher the wider the tunnels
+    pub spaghetti_width: f32,
+    /// Carved blocks at or below WATER_H are flooded where this noise is above aquifer_threshold
+    pub aquifers: Octaves,
+    pub aquifer_threshold: f32,
+    /// Caves shrink over this many blocks under the ground so few of them open on the surface
+    pub surface_fade: i32,
+    /// Caves open on the surface where the cheese noise is above this
+    pub opening_threshold: f32,
+    /// Blocks left above the caves under the sea so it doesn't drain into them
+    pub sea_roof: i32,
+}
+
+impl Default for Caves {
+    fn default() -> Self {
+        Caves {
+            depth: 24,
+            cheese: Octaves(vec![(20., 1.), (60., 0.2)]),
+            cheese_threshold: 0.87,
+            spaghetti: (Octaves(vec![(20., 1.)]), Octaves(vec![(20., 1.)])),
+            spaghetti_width: 0.06,
+            aquifers: Octaves(vec![(10., 1.)]),
+            aquifer_threshold: 0.6,
+            surface_fade: 8,
+            opening_threshold: 0.95,
+            sea_roof: 6,
+        }
+    }
+}
+
+/// Ore veins that show on the walls of rifts at some depths
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OreBand {
+    pub block: Block,
+    /// Depths of the rift (exclusive) where the ore shows
+    pub rift_depth: (i32, i32),
+    pub shape: Octaves,
+    /// Veins are where the noise is above this
+    pub threshold: f32,
+    /// A vein is 1 block high, plus 1 for each of these values the noise is above
+    pub thickening: Vec<f32>,
+}
+
+/// Everything that shapes the Overworld, loaded from EARTH_SETTINGS_FILE unless the world has its own.
+/// Missing fields take the default value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EarthSettings {
+    pub continents: Continents,
+    pub mountains: Mountains,
+    pub rocks: Rocks,
+    pub rifts: Rifts,
+    pub temperature: Octaves,
+    pub humidity: Humidity,
+    pub acidity: Octaves,
+    pub forests: Forests,
+    pub caves: Caves,
+    pub ores: Vec<OreBand>,
+}
+
+impl Default for EarthSettings {
+    fn default() -> Self {
+        EarthSettings {
+            continents: Continents::default(),
+            mountains: Mountains::default(),
+            rocks: Rocks::default(),
+            rifts: Rifts::default(),
+            temperature: Octaves(vec![(0.05, 1.), (0.4, 0.1), (8., 0.05), (100., 0.01)]),
+            humidity: Humidity::default(),
+            acidity: Octaves(vec![(1., 1.), (4., 0.2), (40., 0.1)]),
+            forests: Forests::default(),
+            caves: Caves::default(),
+            ores: vec![OreBand {
+                block: Block::IronOre,
+                rift_depth: (18, 24),
+                shape: Octaves(vec![(8., 1.), (16., 0.1)]),
+                threshold: 0.9,
+                thickening: vec![0.91, 0.94, 0.97],
+            }],
+        }
+    }
+}
+
+impl EarthSettings {
+    pub fn from_file(path: &str) -> Self {
+        json5::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EarthSettings, EARTH_SETTINGS_FILE};
+
+    #[test]
+    fn test_settings_file() {
+        assert_eq!(EarthSettings::from_file(EARTH_SETTINGS_FILE), EarthSettings::default());
+        // missing fields keep their default value
+        let settings: EarthSettings = json5::from_str("{ rifts: { threshold: 0.8 } }").unwrap();
+        assert_eq!(settings.rifts.threshold, 0.8);
+        assert_eq!(settings.rifts.rocky_depth, EarthSettings::default().rifts.rocky_depth);
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use bevy::prelude::warn;
+use crate::world::{Biomes, ColBiomes, ColPos, Realm, VoxelWorld};
+use super::{aether_gen::Aether, debug_gen::DebugGen, earth_gen::Earth, earth_settings::EarthSettings, nether_gen::Nether, superflat_gen::Superflat};
+
+/// Generator used when the world doesn't ask for one, or asks for one that doesn't exist
+pub const DEFAULT_GENERATOR: &str = "earth";
+
+/// Fills a freshly loaded column with terrain, it's called from the gen thread
+pub trait TerrainGenerator: Send + Sync {
+    fn gen(&self, world: &VoxelWorld, col: ColPos);
+
+    /// Stores the biomes of a column that was loaded from disk instead of generated, if the generator has biomes
+    fn gen_biomes(&self, _world: &VoxelWorld, _col: ColPos) {}
+}
+
+impl TerrainGenerator for Earth {
+    fn gen(&self, world: &VoxelWorld, col: ColPos) {
+        Earth::gen(self, world, col)
+    }
+
+    fn gen_biomes(&self, world: &VoxelWorld, col: ColPos) {
+        world.set_col_biomes(col, ColBiomes::new(self.maps(col).biomes));
+    }
+}
+
+impl TerrainGenerator for DebugGen {
+    fn gen(&self, world: &VoxelWorld, col: ColPos) {
+        DebugGen::gen(self, world, col)
+    }
+}
+
+impl TerrainGenerator for Nether {
+    fn gen(&self, world: &VoxelWorld, col: ColPos) {
+        Nether::gen(self, world, col)
+    }
+}
+
+impl TerrainGenerator for Aether {
+    fn gen(&self, world: &VoxelWorld, col: ColPos) {
+        Aether::gen(self, world, col)
+    }
+}
+
+impl TerrainGenerator for Superflat {
+    fn gen(&self, world: &VoxelWorld, col: ColPos) {
+        Superflat::gen(self, world, col)
+    }
+}
+
+/// Leaves every column empty
+pub struct Void;
+
+impl TerrainGenerator for Void {
+    fn gen(&self, _world: &VoxelWorld, _col: ColPos) {}
+}
+
+type GeneratorBuilder = fn(u32, &EarthSettings, &Biomes) -> Box<dyn TerrainGenerator>;
+
+/// The Overworld generators a world can pick by name
+const GENERATORS: [(&str, GeneratorBuilder); 4] = [
+    ("earth", |seed, settings, biomes| Box::new(Earth::new(seed, settings.clone(), biomes.clone()))),
+    ("debug", |seed, _, _| Box::new(DebugGen::new(seed, HashMap::new()))),
+    ("superflat", |_, _, _| Box::new(Superflat)),
+    ("void", |_, _, _| Box::new(Void)),
+];
+
+pub fn generator_names() -> impl Iterator<Item = &'static str> {
+    GENERATORS.iter().map(|(name, _)| *name)
+}
+
+/// The generator of each realm, the Overworld one is chosen by name while the other realms always have their own
+pub struct RealmGenerators(HashMap<Realm, Box<dyn TerrainGenerator>>);
+
+impl RealmGenerators {
+    pub fn new(name: &str, seed: u32, earth_settings: &EarthSettings, biomes: &Biomes) -> Self {
+        let builder = GENERATORS.iter().find(|(gen_name, _)| *gen_name == name).map(|(_, builder)| builder);
+        let builder = builder.unwrap_or_else(|| {
+            warn!("unknown generator '{}', using '{}' (available: {:?})", name, DEFAULT_GENERATOR, generator_names().collect::<Vec<_>>());
+            &GENERATORS[0].1
+        });
+        let mut generators: HashMap<Realm, Box<dyn TerrainGenerator>> = HashMap::new();
+        generators.insert(Realm::Overworld, builder(seed, earth_settings, biomes));
+        generators.insert(Realm::Nether, Box::new(Nether::new(seed)));
+        generators.insert(Realm::Aether, Box::new(Aether::new(seed)));
+        RealmGenerators(generators)
+    }
+
+    pub fn gen(&self, world: &VoxelWorld, col: ColPos) {
+        if let Some(generator) = self.0.get(&col.realm) {
+            generator.gen(world, col);
+        }
+    }
+
+    pub fn gen_biomes(&self, world: &VoxelWorld, col: ColPos) {
+        if let Some(generator) = self.0.get(&col.realm) {
+            generator.gen_biomes(world, col);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::world::{BlockPos, VoxelWorld};
+use crate::world::{BlockChangeCause, BlockPos, VoxelWorld};
 use crate::Block;
 use super::utils::leaf_disk;
 
@@ -6,7 +6,7 @@ pub fn grow_acacia(world: &VoxelWorld, pos: BlockPos, _seed: i32, dist: f32) {
     let height = 10-(dist*7.) as i32;
     let mut pos = pos;
     for _ in 0..height {
-        world.set_block(pos, Block::AcaciaLog);
+        world.set_block(pos, Block::AcaciaLog, BlockChangeCause::Gen);
         pos.y += 1;
     }
 
@@ -1,4 +1,4 @@
-use crate::world::{BlockPos, VoxelWorld};
+use crate::world::{BlockChangeCause, BlockPos, VoxelWorld};
 use crate::Block;
 use super::utils::leaf_disk;
 
@@ -6,7 +6,7 @@ pub fn grow_cypress(world: &VoxelWorld, pos: BlockPos, _seed: i32, dist: f32) {
     let height = 11-(dist*3.) as i32;
     let mut pos = pos;
     for _ in 0..height {
-        world.set_block(pos, Block::SpruceLog);
+        world.set_block(pos, Block::SpruceLog, BlockChangeCause::Gen);
         pos.y += 1;
     }
     pos.y -= height/2;
@@ -14,5 +14,5 @@ pub fn grow_cypress(world: &VoxelWorld, pos: BlockPos, _seed: i32, dist: f32) {
         leaf_disk(world, pos, (1+(i).min(height-i)) as u32/2, Block::SpruceLeaves);
         pos.y += 1;
     }
-    world.set_block(pos, Block::SpruceLeaves);
+    world.set_block(pos, Block::SpruceLeaves, BlockChangeCause::Gen);
 }
@@ -1,11 +1,11 @@
-use crate::world::{BlockPos, VoxelWorld};
+use crate::world::{BlockChangeCause, BlockPos, VoxelWorld};
 use crate::Block;
 use super::utils::leaf_disk;
 const DIRS: [(i32, i32); 8] = [(-1, 1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
 
 fn sequoia_leaves(world: &VoxelWorld, pos: BlockPos, dir_x: i32, dir_z: i32, size: usize) {
     let pos = pos + (if dir_x == 1 {2} else {-1}, 0, if dir_z == 1 {2} else {-1});
-    world.set_block(pos, Block::SequoiaLog);
+    world.set_block(pos, Block::SequoiaLog, BlockChangeCause::Gen);
     leaf_disk(world, pos + (0, -1, 0), 1, Block::SequoiaLeaves);
     leaf_disk(world, pos + (dir_x, 0, dir_z), size as u32, Block::SequoiaLeaves);
 }
@@ -32,11 +32,11 @@ pub fn grow_sequoia(world: &VoxelWorld, pos: BlockPos, seed: i32, dist: f32) {
                 );
             }
         }
-        world.set_block(pos, Block::SequoiaLog);
-        world.set_block(pos + (1, 0, 0), Block::SequoiaLog);
-        world.set_block(pos + (0, 0, 1), Block::SequoiaLog);
-        world.set_block(pos + (1, 0, 1), Block::SequoiaLog);
+        world.set_block(pos, Block::SequoiaLog, BlockChangeCause::Gen);
+        world.set_block(pos + (1, 0, 0), Block::SequoiaLog, BlockChangeCause::Gen);
+        world.set_block(pos + (0, 0, 1), Block::SequoiaLog, BlockChangeCause::Gen);
+        world.set_block(pos + (1, 0, 1), Block::SequoiaLog, BlockChangeCause::Gen);
         pos.y += 1;
     }
-    world.set_block(pos, Block::SpruceLeaves);
+    world.set_block(pos, Block::SpruceLeaves, BlockChangeCause::Gen);
 }
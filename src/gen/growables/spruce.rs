@@ -1,4 +1,4 @@
-use crate::world::{BlockPos, VoxelWorld};
+use crate::world::{BlockChangeCause, BlockPos, VoxelWorld};
 use crate::Block;
 use super::utils::leaf_disk;
 
@@ -9,10 +9,10 @@ pub fn grow_spruce(world: &VoxelWorld, pos: BlockPos, _seed: i32, dist: f32) {
         if i >= 3 && i % 2 == height % 2 {
             leaf_disk(world, pos, ((height-i+2)/2) as u32, Block::SpruceLeaves)
         }
-        world.set_block(pos, Block::SpruceLog);
+        world.set_block(pos, Block::SpruceLog, BlockChangeCause::Gen);
         pos.y += 1;
     }
     leaf_disk(world, pos, 1, Block::SpruceLeaves);
     pos.y += 1;
-    world.set_block(pos, Block::SpruceLeaves);
+    world.set_block(pos, Block::SpruceLeaves, BlockChangeCause::Gen);
 }
@@ -1,4 +1,4 @@
-use crate::world::{BlockPos, VoxelWorld};
+use crate::world::{BlockChangeCause, BlockPos, VoxelWorld};
 use crate::Block;
 
 pub trait Growable: Send + Sync {
@@ -27,7 +27,7 @@ pub fn leaf_disk(world: &VoxelWorld, center: BlockPos, dist: u32, leaf: Block) {
                     x: center.x + dx,
                     y: center.y,
                     z: center.z + dz
-                }, leaf)
+                }, leaf, BlockChangeCause::Gen)
             }
         }
     }
@@ -1,10 +1,20 @@
 mod terrain_gen;
 mod debug_gen;
 mod earth_gen;
+mod earth_settings;
+mod caves;
+mod nether_gen;
+mod aether_gen;
+mod superflat_gen;
+mod generator;
 mod tree;
 mod growables;
 
 pub use terrain_gen::setup_gen_thread;
+pub use generator::{generator_names, RealmGenerators, TerrainGenerator, DEFAULT_GENERATOR};
+pub use earth_settings::{EarthSettings, EARTH_SETTINGS_FILE};
+pub use earth_gen::{Earth, EarthMaps};
+pub use tree::Tree;
 
 use std::ops::Range;
 use crate::Block;
@@ -0,0 +1,61 @@
+use crate::Block;
+use crate::world::{ColPos, VoxelWorld, CHUNK_S1};
+use bevy::prelude::info_span;
+use itertools::iproduct;
+use noise_algebra::NoiseSource;
+use super::earth_gen::pos_to_range;
+
+/// The Nether is one big cave between a bedrock floor at 0 and a bedrock roof at NETHER_H
+pub const NETHER_H: i32 = 192;
+/// Hollows of the cave floor are filled with magma up to this height
+pub const MAGMA_H: i32 = 40;
+
+pub struct Nether {
+    seed: i32,
+}
+
+impl Nether {
+    pub fn new(seed: u32) -> Self {
+        Nether { seed: seed as i32 }
+    }
+
+    pub fn gen(&self, world: &VoxelWorld, col: ColPos) {
+        let range = pos_to_range(col);
+        let gen_span = info_span!("noise gen", name = "noise gen").entered();
+        let mut n = NoiseSource::new(range, self.seed, 1);
+        let floor = (n.simplex(0.5) + n.simplex(4.) * 0.3 + n.simplex(20.) * 0.1).normalize();
+        let ceiling = (n.simplex(0.5) + n.simplex(4.) * 0.3 + n.simplex(20.) * 0.1).normalize();
+        let soil = (n.simplex(2.) + n.simplex(10.) * 0.2).normalize();
+        let glow = (n.simplex(10.) + n.simplex(40.) * 0.3).normalize();
+        // convert to heights, where the floor and the ceiling meet the cave is cut by basalt pillars
+        let floor = floor.map(|f| 8 + (f * 96.) as i32);
+        let ceiling = ceiling.map(|c| NETHER_H - 8 - (c * 96.) as i32);
+        gen_span.exit();
+        let fill_span = info_span!("chunk filling", name = "chunk filling").entered();
+        for (dx, dz) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1) {
+            let (floor_y, ceiling_y, soil, glow) = (floor[[dx, dz]], ceiling[[dx, dz]], soil[[dx, dz]], glow[[dx, dz]]);
+            world.set_yrange(col, (dx, dz), floor_y, floor_y as usize, Block::Basalt);
+            world.set_yrange(col, (dx, dz), NETHER_H, (NETHER_H - ceiling_y) as usize, Block::Basalt);
+            world.set_yrange(col, (dx, dz), 0, 1, Block::Bedrock);
+            world.set_yrange(col, (dx, dz), NETHER_H, 1, Block::Bedrock);
+            if floor_y >= ceiling_y {
+                continue;
+            }
+            if soil > 0.8 {
+                world.set_yrange(col, (dx, dz), floor_y, 2, Block::SoulSoil);
+            }
+            let magma_top = MAGMA_H.min(ceiling_y - 1);
+            if floor_y < magma_top {
+                world.set_yrange(col, (dx, dz), magma_top, (magma_top - floor_y) as usize, Block::Magma);
+            }
+            // glowstone hangs from the ceiling
+            if glow > 0.9 {
+                let length = ((glow - 0.9) * 80.) as i32;
+                if ceiling_y - length > magma_top.max(floor_y) + 3 {
+                    world.set_yrange(col, (dx, dz), ceiling_y - 1, length as usize, Block::Glowstone);
+                }
+            }
+        }
+        fill_span.exit();
+    }
+}
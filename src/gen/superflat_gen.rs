@@ -0,0 +1,20 @@
+use crate::Block;
+use crate::world::{ColPos, VoxelWorld, CHUNK_S1, WATER_H};
+use itertools::iproduct;
+
+/// Height of the grass of superflat worlds
+pub const SUPERFLAT_H: i32 = WATER_H + 3;
+
+/// The same layers of bedrock, granite, dirt and grass everywhere
+pub struct Superflat;
+
+impl Superflat {
+    pub fn gen(&self, world: &VoxelWorld, col: ColPos) {
+        for (dx, dz) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1) {
+            world.set_yrange(col, (dx, dz), SUPERFLAT_H - 4, (SUPERFLAT_H - 4) as usize, Block::Granite);
+            world.set_yrange(col, (dx, dz), SUPERFLAT_H - 1, 2, Block::Dirt);
+            world.set_yrange(col, (dx, dz), SUPERFLAT_H, 1, Block::GrassBlock);
+            world.set_yrange(col, (dx, dz), 0, 1, Block::Bedrock);
+        }
+    }
+}
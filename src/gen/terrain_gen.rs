@@ -1,30 +1,46 @@
-use crate::gen::earth_gen::Earth;
-use crate::world::VoxelWorld;
+use crate::gen::{EarthSettings, RealmGenerators, EARTH_SETTINGS_FILE};
+use crate::world::{Biomes, Regions, VoxelWorld};
 use crate::WorldRng;
+use crate::save::WorldSave;
 use bevy::ecs::system::Res;
 use bevy::tasks::AsyncComputeTaskPool;
-use std::collections::HashMap;
 use std::sync::Arc;
 use std::thread::yield_now;
 use crate::world::LoadOrders;
 
-pub fn setup_gen_thread(blocks: Res<VoxelWorld>, world_rng: Res<WorldRng>, load_orders: Res<LoadOrders>) {
+pub fn setup_gen_thread(blocks: Res<VoxelWorld>, world_rng: Res<WorldRng>, load_orders: Res<LoadOrders>, regions: Res<Regions>, world_save: Res<WorldSave>, biomes: Res<Biomes>) {
     let thread_pool = AsyncComputeTaskPool::get();
-    let chunks = Arc::clone(&blocks.chunks);
+    let world = blocks.clone();
     let seed_value = world_rng.seed;
+    let loaded_cols = Arc::clone(&load_orders.loaded);
     let load_orders = Arc::clone(&load_orders.to_generate);
+    let regions = regions.clone();
+    let earth_settings = world_save.meta.earth_settings.clone()
+        .unwrap_or_else(|| EarthSettings::from_file(EARTH_SETTINGS_FILE));
+    let generator = world_save.meta.generator.clone();
+    let biomes = biomes.clone();
     thread_pool.spawn(
         async move {
-            let gen = Earth::new(seed_value as u32, HashMap::new());
-            let world = VoxelWorld::new_with(chunks);
+            let generators = RealmGenerators::new(&generator, seed_value as u32, &earth_settings, &biomes);
             loop {
                 let Some((col_pos, _)) = load_orders.try_write_arc().and_then(|mut ld| ld.pop()) else {
                     yield_now();
                     continue;
                 };
-                gen.gen(&world, col_pos);
+                // columns that were edited and saved are loaded from disk, the others are generated
+                if regions.load_col(&world, col_pos) {
+                    generators.gen_biomes(&world, col_pos);
+                } else {
+                    generators.gen(&world, col_pos);
+                    world.clear_edited_col(col_pos);
+                    world.compact_col(col_pos);
+                }
+                world.compute_heightmaps(col_pos);
+                world.light_col(col_pos);
                 world.mark_change_col(col_pos);
+                world.mark_change_neighbour_cols(col_pos);
+                loaded_cols.push(col_pos);
             }
         }
     ).detach();
-}
\ No newline at end of file
+}
@@ -27,6 +27,15 @@ pub enum BlockKind {
     Family(BlockFamily),
 }
 
+impl BlockKind {
+    pub fn matches(&self, block: &Block) -> bool {
+        match self {
+            BlockKind::Block(kind_block) => kind_block == block,
+            BlockKind::Family(family) => block.families().contains(family),
+        }
+    }
+}
+
 impl FromStr for BlockKind {
     type Err = json5::Error;
 
@@ -0,0 +1,26 @@
+pub mod asset_processing;
+pub mod block;
+pub mod items;
+pub mod ui;
+pub mod world;
+pub mod render;
+pub mod agents;
+pub mod sounds;
+pub mod gen;
+pub mod save;
+include!(concat!(env!("OUT_DIR"), "/blocks.rs"));
+use bevy::prelude::Resource;
+use rand_chacha::ChaCha8Rng;
+
+#[derive(Resource)]
+pub struct WorldRng {
+    pub seed: u64,
+    pub rng: ChaCha8Rng
+}
+
+/// The value following the command line flag `name`, e.g. `--seed 42`
+pub fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args();
+    args.find(|arg| arg == name)?;
+    args.next()
+}
@@ -1,28 +1,10 @@
-mod asset_processing;
-mod block;
-mod items;
-mod ui;
-mod world;
-mod render;
-mod agents;
-mod sounds;
-mod gen;
-include!(concat!(env!("OUT_DIR"), "/blocks.rs"));
 use bevy::{prelude::*, render::texture::{ImageAddressMode, ImageFilterMode, ImageSamplerDescriptor}};
-use world::VoxelWorld;
-use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
-use sounds::SoundPlugin;
-use ui::UIPlugin;
-use render::{Render, TextureLoadPlugin};
-use agents::{MovementPlugin, PlayerPlugin};
-use world::GenPlugin;
-const SEED: u64 = 42;
-
-#[derive(Resource)]
-pub struct WorldRng {
-    pub seed: u64,
-    pub rng: ChaCha8Rng
-}
+use riverbed::world::{GenPlugin, VoxelWorld};
+use riverbed::sounds::SoundPlugin;
+use riverbed::ui::UIPlugin;
+use riverbed::render::{Render, TextureLoadPlugin};
+use riverbed::agents::{MovementPlugin, PlayerPlugin};
+use riverbed::save::SavePlugin;
 
 fn main() {
     let mut app = App::new();
@@ -48,10 +30,7 @@ fn main() {
                 },
             })
         )
-        .insert_resource(WorldRng {
-            seed: SEED,
-            rng: ChaCha8Rng::seed_from_u64(SEED)
-        })
+        .add_plugins(SavePlugin)
         .add_plugins(PlayerPlugin)
         .add_plugins(TextureLoadPlugin)
         .add_plugins(UIPlugin)
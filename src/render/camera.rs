@@ -1,7 +1,7 @@
 use std::f32::consts::FRAC_PI_2;
 use bevy::{pbr::ScreenSpaceAmbientOcclusionBundle, prelude::*};
 use bevy::window::CursorGrabMode;
-use crate::{agents::{PlayerControlled, PlayerSpawn, AABB}, ui::CursorGrabbed};
+use crate::{agents::{PlayerControlled, PlayerSpawn, AABB}, save::WorldSave, ui::CursorGrabbed};
 use leafwing_input_manager::prelude::*;
 
 const CAMERA_PAN_RATE: f32 = 0.06;
@@ -39,7 +39,12 @@ pub struct FpsCam {
 #[derive(SystemSet, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct CameraSpawn;
 
-pub fn cam_setup(mut commands: Commands, mut windows: Query<&mut Window>, player_query: Query<(Entity, &AABB), With<PlayerControlled>>) {
+pub fn cam_setup(
+    mut commands: Commands, 
+    mut windows: Query<&mut Window>, 
+    player_query: Query<(Entity, &AABB), With<PlayerControlled>>,
+    world_save: Res<WorldSave>,
+) {
     let input_map = InputMap::default()
         // This will capture the total continuous value, for direct use.
         // Note that you can also use discrete gesture-like motion,
@@ -74,7 +79,10 @@ pub fn cam_setup(mut commands: Commands, mut windows: Query<&mut Window>, player
             input_map,
             ..default()
         })
-        .insert(FpsCam::default())
+        .insert(world_save.meta.player.as_ref().map(|player_save| FpsCam {
+            yaw: player_save.yaw, 
+            pitch: player_save.pitch
+        }).unwrap_or_default())
         .id();
     commands.entity(player).add_child(cam);
     let mut window = windows.single_mut();
@@ -12,8 +12,10 @@ use strum::IntoEnumIterator;
 use crate::block::Face;
 use crate::world::pos2d::chunks_in_col;
 use crate::world::{VoxelWorld, ChunkPos, CHUNK_S1, Y_CHUNKS};
+use crate::agents::PlayerControlled;
 use crate::world::{range_around, ColUnloadEvent, PlayerArea, LoadAreaAssigned};
 use super::chunk_culling::chunk_culling;
+use super::mesh_chunks::{light_volume, neighbour_layers};
 use super::shared_load_area::{setup_shared_load_area, update_shared_load_area, SharedLoadArea};
 use super::texture_array::BlockTextureArray;
 use super::BlockTexState;
@@ -31,7 +33,7 @@ fn choose_lod_level(chunk_dist: u32) -> usize {
 }
 
 fn mark_lod_remesh(
-    load_area: Res<PlayerArea>, 
+    load_area_query: Query<&PlayerArea, (With<PlayerControlled>, Changed<PlayerArea>)>, 
     chunk_ents: ResMut<ChunkEntities>, 
     lods: Query<&LOD>, 
     blocks: ResMut<VoxelWorld>
@@ -39,7 +41,7 @@ fn mark_lod_remesh(
     // FIXME: this only remesh chunks that previously had a mesh 
     // However in some rare cases a chunk with some blocs can produce an empty mesh at certain LODs 
     // and never get remeshed even though it should
-    if !load_area.is_changed() { return; }
+    let Ok(load_area) = load_area_query.get_single() else { return; };
     for ((chunk_pos, _), entity) in chunk_ents.0.iter().unique_by(|((chunk_pos, _), _)| chunk_pos) {
         let Some(dist) =  load_area.col_dists.get(&(*chunk_pos).into()) else {
             continue;
@@ -57,7 +59,8 @@ fn mark_lod_remesh(
     }
 }
 
-fn chunk_aabb_gizmos(mut gizmos: Gizmos, load_area: Res<PlayerArea>) {
+fn chunk_aabb_gizmos(mut gizmos: Gizmos, load_area_query: Query<&PlayerArea, With<PlayerControlled>>) {
+    let Ok(load_area) = load_area_query.get_single() else { return; };
     for (x, y) in iproduct!(range_around(load_area.center.x, GRID_GIZMO_LEN), 0..=Y_CHUNKS) {
         let start = Vec3::new(x as f32, y as f32, (load_area.center.z-GRID_GIZMO_LEN) as f32)*CHUNK_S1 as f32;
         let end = Vec3::new(x as f32, y as f32, (load_area.center.z+GRID_GIZMO_LEN) as f32)*CHUNK_S1 as f32;
@@ -96,10 +99,12 @@ fn setup_mesh_thread(mut commands: Commands, blocks: Res<VoxelWorld>, shared_loa
                     continue;
                 };
                 let lod = choose_lod_level(dist);
+                let neighbours = neighbour_layers(&chunks, chunk_pos, lod);
+                let light = light_volume(&chunks, chunk_pos);
                 let Some(chunk) = chunks.get(&chunk_pos) else {
                     continue;
                 };
-                let face_meshes = chunk.create_face_meshes(&*texture_map, lod);
+                let face_meshes = chunk.create_face_meshes(&*texture_map, lod, &neighbours, &light);
                 for (i, face_mesh) in face_meshes.into_iter().enumerate() {
                     let face = i.into();
                     if mesh_sender.send((face_mesh, chunk_pos, face, LOD(lod))).is_err() {
@@ -118,9 +123,10 @@ pub fn pull_meshes(
     mut mesh_query: Query<(&mut Handle<Mesh>, &mut LOD)>,
     mut meshes: ResMut<Assets<Mesh>>,
     block_tex_array: Res<BlockTextureArray>,
-    load_area: Res<PlayerArea>,
+    load_area_query: Query<&PlayerArea, With<PlayerControlled>>,
     blocks: Res<VoxelWorld>
 ) {
+    let Ok(load_area) = load_area_query.get_single() else { return; };
     let received_meshes: Vec<_> = mesh_reciever.0.try_iter()
         .filter(|(_, chunk_pos, _, _)| load_area.col_dists.contains_key(&(*chunk_pos).into()))
         .collect();
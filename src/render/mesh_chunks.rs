@@ -7,8 +7,11 @@ use bevy::{
     render_resource::{PrimitiveTopology, VertexFormat}}
 };
 use binary_greedy_meshing as bgm;
+use dashmap::DashMap;
+use itertools::iproduct;
+use strum::IntoEnumIterator;
 
-use crate::{Block, block::Face, world::{pad_linearize, Chunk, CHUNKP_S3}};
+use crate::{Block, block::{BlockState, Face}, world::{light_level, linearize, pad_linearize, Chunk, ChunkPos, Light, TrackedChunk, CHUNKP_S1, CHUNKP_S3, SKY_LIGHT}};
 use crate::world::CHUNK_S1;
 use super::texture_array::TextureMapTrait;
 
@@ -32,9 +35,158 @@ pub const ATTRIBUTE_VOXEL_DATA: MeshVertexAttribute =
     MeshVertexAttribute::new("VoxelData", 48757581, VertexFormat::Uint32x2);
 
 
+// Position of the (i, j) voxel of a layer orthogonal to the face normal
+fn layer_pos(face: Face, i: usize, j: usize, layer: usize) -> (usize, usize, usize) {
+    match face {
+        Face::Left | Face::Right => (layer, i, j),
+        Face::Down | Face::Up => (i, layer, j),
+        Face::Back | Face::Front => (i, j, layer),
+    }
+}
+
+/// Runs f on the chunk touching `face` of the chunk, None if it isn't loaded.
+/// The guard only lives for the call so we never hold 2 chunks at once
+fn with_neighbour<T>(
+    chunks: &DashMap<ChunkPos, TrackedChunk>, chunk_pos: ChunkPos, face: Face, f: impl FnOnce(&TrackedChunk) -> T
+) -> Option<T> {
+    let [dx, dy, dz] = face.n();
+    let neighbour_pos = ChunkPos {
+        x: chunk_pos.x + dx,
+        y: chunk_pos.y + dy,
+        z: chunk_pos.z + dz,
+        realm: chunk_pos.realm,
+    };
+    chunks.get(&neighbour_pos).map(|chunk| f(&chunk))
+}
+
+/// For each face (in Face::iter() order), the layer of the neighbouring chunk touching it, 
+/// or None if the neighbour isn't loaded
+pub fn neighbour_layers(
+    chunks: &DashMap<ChunkPos, TrackedChunk>, chunk_pos: ChunkPos, lod: usize
+) -> [Option<Vec<Block>>; 6] {
+    let mut layers = core::array::from_fn(|_| None);
+    for (i, face) in Face::iter().enumerate() {
+        layers[i] = with_neighbour(chunks, chunk_pos, face, |chunk| chunk.face_layer(face.opposite(), lod));
+    }
+    layers
+}
+
+/// Padded light of the chunk, the padding holds the light of the neighbouring chunks touching its faces.
+/// Chunks that don't exist are above the terrain, so fully sky lit.
+pub fn light_volume(chunks: &DashMap<ChunkPos, TrackedChunk>, chunk_pos: ChunkPos) -> Vec<Light> {
+    let mut light = vec![SKY_LIGHT; CHUNKP_S3];
+    if let Some(chunk) = chunks.get(&chunk_pos) {
+        for (x, y, z) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1, 0..CHUNK_S1) {
+            light[pad_linearize(x, y, z)] = chunk.light.get((x, y, z));
+        }
+    }
+    for face in Face::iter() {
+        let (pad, layer) = if face.is_positive() { (CHUNKP_S1-1, 0) } else { (0, CHUNK_S1-1) };
+        with_neighbour(chunks, chunk_pos, face, |neighbour| {
+            for (i, j) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1) {
+                let (x, y, z) = layer_pos(face, i+1, j+1, pad);
+                light[linearize(x, y, z)] = neighbour.light.get(layer_pos(face, i, j, layer));
+            }
+        });
+    }
+    light
+}
+
+// Light of a vertex of a face, the brightest of the 4 voxels touching it in front of the face
+fn vertex_light(light: &[Light], face: Face, vertex: u32) -> u32 {
+    // vertex positions are in unpadded chunk coordinates, so they're the padded coordinates of the voxel after the corner
+    let pos = [vertex & 0b111111, (vertex >> 6) & 0b111111, (vertex >> 12) & 0b111111].map(|c| c as usize);
+    let axis = face.n().iter().position(|c| *c != 0).unwrap();
+    let front = if face.is_positive() { pos[axis] + 1 } else { pos[axis] };
+    iproduct!(0..2, 0..2).map(|(di, dj)| {
+        let mut voxel = pos;
+        voxel[axis] = front;
+        voxel[(axis+1)%3] += di;
+        voxel[(axis+2)%3] += dj;
+        light_level(light[linearize(voxel[0], voxel[1], voxel[2])])
+    }).max().unwrap() as u32
+}
+
+/// Cuts the part of a quad that is outside of the n voxels of a chunk meshed at some lod, None if nothing is left.
+/// With lod > 1 the padding layers of the positive faces are inside the meshed volume,
+/// so their own faces are meshed and faces of the chunk can be merged with them.
+fn truncate_quad(face: Face, xyz: u64, w: u64, h: u64, n: u64) -> Option<(u64, u64, u64)> {
+    let mut pos = [xyz & MASK_6, (xyz >> 6) & MASK_6, (xyz >> 12) & MASK_6];
+    let axis = face.n().iter().position(|c| *c != 0).unwrap();
+    // quads of positive faces are on the far side of their voxel
+    let voxel = if face.is_positive() { pos[axis].saturating_sub(1) } else { pos[axis] };
+    if voxel >= n {
+        return None;
+    }
+    // the width of these faces goes backwards from pos, see Face::vertices_packed
+    let (w_axis, h_axis, w_backwards) = match face {
+        Face::Left => (1, 2, false),
+        Face::Down => (0, 2, true),
+        Face::Back => (0, 1, false),
+        Face::Right => (1, 2, true),
+        Face::Up => (0, 2, false),
+        Face::Front => (0, 1, true),
+    };
+    let w = if w_backwards {
+        let end = pos[w_axis].min(n);
+        let w = w.saturating_sub(pos[w_axis] - end);
+        pos[w_axis] = end;
+        w
+    } else {
+        w.min(n.saturating_sub(pos[w_axis]))
+    };
+    let h = h.min(n.saturating_sub(pos[h_axis]));
+    if w == 0 || h == 0 {
+        return None;
+    }
+    Some((pos[0] | (pos[1] << 6) | (pos[2] << 12), w, h))
+}
+
 impl Chunk {
+    /// The blocks of the layer of the chunk touching `face`, downsampled to `lod`
+    pub fn face_layer(&self, face: Face, lod: usize) -> Vec<Block> {
+        let n = CHUNK_S1/lod;
+        if let Chunk::Uniform(block) = self {
+            return vec![*block; n*n];
+        }
+        let layer = if face.is_positive() { CHUNK_S1-1 } else { 0 };
+        iproduct!(0..n, 0..n).map(|(i, j)| 
+            // same as voxel_data_lod, the first non-air block of the lod cell is kept
+            iproduct!(0..lod, 0..lod)
+                .map(|(di, dj)| *self.get(layer_pos(face, i*lod+di, j*lod+dj, layer)))
+                .find(|block| *block != Block::Air)
+                .unwrap_or(Block::Air)
+        ).collect()
+    }
+
+    /// Writes the neighbour layers in the padding of the voxel data so that faces hidden by
+    /// neighbouring chunks are culled. Neighbour blocks are appended to the palette if needed.
+    fn fill_padding(voxels: &mut [u16], palette: &mut Vec<(Block, BlockState)>, neighbours: &[Option<Vec<Block>>; 6], lod: usize) {
+        let n = CHUNK_S1/lod;
+        for (face, layer) in Face::iter().zip(neighbours) {
+            let Some(layer) = layer else {
+                continue;
+            };
+            // with lod > 1 the upper layers are inside the meshed volume, their quads are cut by truncate_quad
+            let pad = if face.is_positive() { n+1 } else { 0 };
+            for (i, j) in iproduct!(0..n, 0..n) {
+                let block = layer[i*n+j];
+                // the state of padding blocks doesn't matter, only their opacity does
+                let index = match palette.iter().position(|(b, _)| *b == block) {
+                    Some(index) => index,
+                    None => {
+                        palette.push((block, 0));
+                        palette.len()-1
+                    }
+                };
+                let (x, y, z) = layer_pos(face, i+1, j+1, pad);
+                voxels[linearize(x, y, z)] = index as u16;
+            }
+        }
+    }
+
     pub fn voxel_data_lod(&self, lod: usize) -> Vec<u16> {
-        let voxels = self.data.unpack_u16();
+        let voxels = self.voxels();
         if lod == 1 {
             return voxels;
         }
@@ -54,15 +206,25 @@ impl Chunk {
 
     /// Doesn't work with lod > 2, because chunks are of size 62 (to get to 64 with padding) and 62 = 2*31
     /// TODO: make it work with lod > 2 if necessary (by truncating quads)
-    pub fn create_face_meshes(&self, texture_map: impl TextureMapTrait, lod: usize) ->  [Option<Mesh>; 6] {
+    /// `neighbours` are the layers of the neighbouring chunks as returned by `neighbour_layers`,
+    /// `light` is the light of the chunk as returned by `light_volume`
+    pub fn create_face_meshes(
+        &self, texture_map: impl TextureMapTrait, lod: usize, neighbours: &[Option<Vec<Block>>; 6], light: &[Light]
+    ) ->  [Option<Mesh>; 6] {
+        if let Chunk::Uniform(Block::Air) = self {
+            return core::array::from_fn(|_| None);
+        }
         // Gathering binary greedy meshing input data
         let mesh_data_span = info_span!("mesh voxel data", name = "mesh voxel data").entered();
-        let voxels = self.voxel_data_lod(lod);
+        let mut voxels = self.voxel_data_lod(lod);
+        let mut palette = self.palette_blocks();
+        Chunk::fill_padding(&mut voxels, &mut palette, neighbours, lod);
+        let n = (CHUNK_S1/lod) as u64;
         let mut mesh_data = bgm::MeshData::new();
         mesh_data_span.exit();
         let mesh_build_span = info_span!("mesh build", name = "mesh build").entered();
-        let transparents = BTreeSet::from_iter(self.palette.iter().enumerate().filter_map(
-            |(i, block)| if i != 0 && !block.is_opaque() {
+        let transparents = BTreeSet::from_iter(palette.iter().enumerate().filter_map(
+            |(i, (block, _))| if i != 0 && !block.is_opaque() {
                 Some(i as u16)
             } else {
                 None
@@ -72,15 +234,17 @@ impl Chunk {
         let mut meshes = core::array::from_fn(|_| None);
         for (face_n, quads) in mesh_data.quads.iter().enumerate() {
             let mut voxel_data: Vec<[u32; 2]> = Vec::with_capacity(quads.len()*4);
-            let indices = bgm::indices(quads.len());
             let face: Face = face_n.into();
             for quad in quads {
                 let voxel_i = (quad >> 32) as usize;
                 let w = MASK_6 & (quad >> 18);
                 let h = MASK_6 & (quad >> 24);
                 let xyz = MASK_XYZ & quad;
-                let block = self.palette[voxel_i];
-                let layer = texture_map.get_texture_index(block, face) as u32;
+                let Some((xyz, w, h)) = truncate_quad(face, xyz, w, h, n) else {
+                    continue;
+                };
+                let (block, state) = palette[voxel_i];
+                let layer = texture_map.get_texture_index(block, block.textured_face(state, face)) as u32;
                 let color = match (block, face) {
                     (Block::GrassBlock, Face::Up) => 0b011_111_001,
                     (Block::SeaBlock, _) => 0b110_011_001,
@@ -89,13 +253,11 @@ impl Chunk {
                 };
                 let vertices = face.vertices_packed(xyz as u32, w as u32, h as u32, lod as u32);
                 let quad_info = (layer << 12) | (color << 3) | face_n as u32;
-                voxel_data.extend_from_slice(&[
-                    [vertices[0], quad_info], 
-                    [vertices[1], quad_info], 
-                    [vertices[2], quad_info], 
-                    [vertices[3], quad_info]
-                ]);
+                voxel_data.extend(vertices.map(
+                    |vertex| [vertex, (vertex_light(light, face, vertex) << 28) | quad_info]
+                ));
             }
+            let indices = bgm::indices(voxel_data.len()/4);
             meshes[face_n] = Some(
                 Mesh::new(
                     PrimitiveTopology::TriangleList,
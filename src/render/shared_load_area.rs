@@ -1,17 +1,22 @@
 use std::sync::Arc;
 use parking_lot::RwLock;
-use bevy::ecs::{change_detection::DetectChanges, system::{Commands, Res, Resource}};
-use crate::world::PlayerArea;
+use bevy::ecs::{query::{Changed, With}, system::{Commands, Query, Res, Resource}};
+use crate::{agents::PlayerControlled, world::PlayerArea};
 
 
+/// The load area of the player we're rendering for, shared with the mesh thread
 #[derive(Resource)]
 pub struct SharedLoadArea(pub Arc<RwLock<PlayerArea>>);
 
-pub fn setup_shared_load_area(mut commands: Commands, load_area: Res<PlayerArea>) {
-    commands.insert_resource(SharedLoadArea(Arc::new(RwLock::new(load_area.clone()))))
+pub fn setup_shared_load_area(mut commands: Commands, load_area_query: Query<&PlayerArea, With<PlayerControlled>>) {
+    let load_area = load_area_query.get_single().cloned().unwrap_or_else(|_| PlayerArea::empty());
+    commands.insert_resource(SharedLoadArea(Arc::new(RwLock::new(load_area))))
 }
 
-pub fn update_shared_load_area(load_area: Res<PlayerArea>, shared_load_area: Res<SharedLoadArea>) {
-    if !load_area.is_changed() { return; }
+pub fn update_shared_load_area(
+    load_area_query: Query<&PlayerArea, (With<PlayerControlled>, Changed<PlayerArea>)>,
+    shared_load_area: Res<SharedLoadArea>
+) {
+    let Ok(load_area) = load_area_query.get_single() else { return; };
     *shared_load_area.0.write() = load_area.clone();
-}
\ No newline at end of file
+}
@@ -0,0 +1,2 @@
+mod world_save;
+pub use world_save::*;
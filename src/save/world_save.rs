@@ -0,0 +1,187 @@
+use std::{fs, io::ErrorKind, path::{Path, PathBuf}};
+use bevy::{app::AppExit, prelude::*};
+use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+use serde::{Deserialize, Serialize};
+use crate::{
+    agents::PlayerControlled, gen::{generator_names, EarthSettings, DEFAULT_GENERATOR, EARTH_SETTINGS_FILE}, items::Stack, render::FpsCam, ui::ItemHolder,
+    world::{ColUnloadEvent, Realm, Regions, RenderDistance, ScheduledTick, ScheduledTicks, WorldClock}, arg_value, WorldRng
+};
+const SAVES_DIR: &str = "saves";
+const WORLD_FILE: &str = "world.json5";
+const REGIONS_DIR: &str = "regions";
+const DEFAULT_WORLD: &str = "default";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlayerSave {
+    pub realm: Realm,
+    pub translation: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub render_distance: u32,
+    pub inventory: Vec<Stack>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorldMeta {
+    pub seed: u64,
+    // name of the Overworld generator, see gen::generator_names
+    #[serde(default = "default_generator")]
+    pub generator: String,
+    // EARTH_SETTINGS_FILE as it was when the world was created, editing the file must not change existing worlds
+    #[serde(default)]
+    pub earth_settings: Option<EarthSettings>,
+    // None until the world has been played once
+    #[serde(default)]
+    pub player: Option<PlayerSave>,
+    // game ticks elapsed, see WorldClock
+    #[serde(default)]
+    pub clock: u64,
+    #[serde(default)]
+    pub block_ticks: Vec<ScheduledTick>,
+}
+
+fn default_generator() -> String {
+    DEFAULT_GENERATOR.to_string()
+}
+
+/// The world folder: `saves/<name>/world.json5` + region files
+#[derive(Resource)]
+pub struct WorldSave {
+    pub dir: PathBuf,
+    pub meta: WorldMeta,
+}
+
+impl WorldSave {
+    /// Opens the world `name`, creating it with `seed` (or a random one) and `generator` if it doesn't exist,
+    /// fails if the generator is unknown or if the world file can't be read or parsed so that it never gets overwritten
+    pub fn open(name: &str, seed: Option<u64>, generator: Option<String>) -> Result<Self, String> {
+        if let Some(generator) = &generator {
+            if !generator_names().any(|gen_name| gen_name == generator) {
+                return Err(format!(
+                    "unknown generator '{}' (available: {:?})", generator, generator_names().collect::<Vec<_>>()
+                ));
+            }
+        }
+        let dir = Path::new(SAVES_DIR).join(name);
+        let path = dir.join(WORLD_FILE);
+        let meta = match fs::read_to_string(&path) {
+            Ok(content) => {
+                let mut meta = json5::from_str::<WorldMeta>(&content)
+                    .map_err(|err| format!("couldn't open world {:?}: {}", path, err))?;
+                // worlds saved before the settings were stored keep the current ones from now on
+                meta.earth_settings.get_or_insert_with(|| EarthSettings::from_file(EARTH_SETTINGS_FILE));
+                if let Some(generator) = generator.filter(|generator| *generator != meta.generator) {
+                    warn!("world '{}' was created with the '{}' generator, ignoring --gen {}", name, meta.generator, generator);
+                }
+                meta
+            },
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                return Err(format!("couldn't open world {:?}: {}", path, err));
+            },
+            Err(_) => WorldMeta {
+                seed: seed.unwrap_or_else(rand::random),
+                generator: generator.unwrap_or_else(default_generator),
+                earth_settings: Some(EarthSettings::from_file(EARTH_SETTINGS_FILE)),
+                player: None,
+                clock: 0,
+                block_ticks: Vec::new(),
+            }
+        };
+        Ok(WorldSave { dir, meta })
+    }
+
+    pub fn regions_dir(&self) -> PathBuf {
+        self.dir.join(REGIONS_DIR)
+    }
+
+    pub fn write(&self) {
+        if let Err(err) = fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(self.dir.join(WORLD_FILE), json5::to_string(&self.meta).unwrap())) 
+        {
+            println!("couldn't save world {:?}: {}", self.dir, err);
+        }
+    }
+}
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        let name = arg_value("--world").unwrap_or(DEFAULT_WORLD.to_string());
+        let seed = arg_value("--seed").and_then(|seed| seed.parse().ok());
+        let generator = arg_value("--gen");
+        let mut world_save = match WorldSave::open(&name, seed, generator) {
+            Ok(world_save) => world_save,
+            Err(err) => {
+                error!("{}", err);
+                std::process::exit(1);
+            }
+        };
+        // write the metadata right away so the region files never exist without it
+        world_save.write();
+        app
+            .insert_resource(WorldClock::new(world_save.meta.clock))
+            .insert_resource(ScheduledTicks::from(std::mem::take(&mut world_save.meta.block_ticks)))
+            .insert_resource(WorldRng {
+                seed: world_save.meta.seed,
+                rng: ChaCha8Rng::seed_from_u64(world_save.meta.seed)
+            })
+            .insert_resource(Regions::new(world_save.regions_dir()))
+            .insert_resource(world_save)
+            .add_systems(Last, (save_ticks_on_unload, save_meta_on_exit).chain())
+            ;
+    }
+}
+
+// columns are written to their region when they unload, the ticks scheduled in them must be on disk too
+fn save_ticks_on_unload(
+    mut ev_unload: EventReader<ColUnloadEvent>,
+    mut world_save: ResMut<WorldSave>,
+    clock: Res<WorldClock>,
+    block_ticks: Res<ScheduledTicks>,
+) {
+    if ev_unload.read().last().is_none() {
+        return;
+    }
+    world_save.meta.clock = clock.tick;
+    world_save.meta.block_ticks = block_ticks.to_vec();
+    world_save.write();
+}
+
+fn save_meta_on_exit(
+    mut ev_exit: EventReader<AppExit>,
+    mut world_save: ResMut<WorldSave>,
+    clock: Res<WorldClock>,
+    block_ticks: Res<ScheduledTicks>,
+    player_query: Query<(&Transform, &Realm, &RenderDistance, &ItemHolder), With<PlayerControlled>>,
+    cam_query: Query<&FpsCam>,
+) {
+    if ev_exit.read().last().is_none() {
+        return;
+    }
+    world_save.meta.clock = clock.tick;
+    world_save.meta.block_ticks = block_ticks.to_vec();
+    let Ok((transform, realm, render_dist, item_holder)) = player_query.get_single() else {
+        world_save.write();
+        return;
+    };
+    let fps_cam = cam_query.get_single().copied().unwrap_or_default();
+    // the player should always hold an inventory, save an empty one rather than losing the rest
+    let inventory: &[Stack] = match item_holder {
+        ItemHolder::Inventory(inventory) => &inventory[..],
+        _ => &[],
+    };
+    world_save.meta.player = Some(PlayerSave {
+        realm: *realm,
+        translation: transform.translation.to_array(),
+        yaw: fps_cam.yaw,
+        pitch: fps_cam.pitch,
+        render_distance: render_dist.0,
+        // Stack is intentionally not Clone, we rebuild the stacks for the save
+        inventory: inventory.iter().map(|stack| match stack {
+            Stack::Some(item, qty) => Stack::Some(*item, *qty),
+            Stack::None => Stack::None,
+        }).collect(),
+    });
+    world_save.write();
+}
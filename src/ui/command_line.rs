@@ -0,0 +1,125 @@
+use std::time::Duration;
+use bevy::{color::palettes::css, input::{keyboard::{Key, KeyboardInput}, ButtonState}, prelude::*};
+
+use super::{game_menu::despawn_screen, GameUiState};
+
+pub struct CommandLinePlugin;
+
+impl Plugin for CommandLinePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<CommandSubmitted>()
+            .add_event::<CommandOutput>()
+            .add_systems(Startup, setup_command_output)
+            .add_systems(Update, show_command_output)
+            .add_systems(OnEnter(GameUiState::CommandLine), setup_command_line)
+            .add_systems(OnExit(GameUiState::CommandLine), despawn_screen::<CommandLineText>)
+            .add_systems(Update, type_command)
+            ;
+    }
+}
+
+/// A command typed by the player, without the leading '/'
+#[derive(Event, Debug, Clone)]
+pub struct CommandSubmitted(pub String);
+
+/// The result of a command, shown to the player for a few seconds
+#[derive(Event, Debug, Clone)]
+pub struct CommandOutput(pub String);
+
+// how long the result of a command stays on screen
+const OUTPUT_DURATION: Duration = Duration::from_secs(5);
+
+#[derive(Component)]
+struct CommandLineText;
+
+#[derive(Component)]
+struct CommandOutputText(Timer);
+
+fn setup_command_output(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/RobotoMono-Light.ttf"),
+                font_size: 20.0,
+                color: Color::Srgba(css::BEIGE),
+            },
+        ).with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(40.),
+            left: Val::Px(10.),
+            ..default()
+        }),
+        CommandOutputText(Timer::new(OUTPUT_DURATION, TimerMode::Once)),
+    ));
+}
+
+fn show_command_output(
+    mut outputs: EventReader<CommandOutput>,
+    mut text_query: Query<(&mut Text, &mut CommandOutputText)>,
+    time: Res<Time>,
+) {
+    let Ok((mut text, mut output_text)) = text_query.get_single_mut() else {
+        return;
+    };
+    if let Some(CommandOutput(output)) = outputs.read().last() {
+        text.sections[0].value = output.clone();
+        output_text.0.reset();
+    } else if output_text.0.tick(time.delta()).just_finished() {
+        text.sections[0].value.clear();
+    }
+}
+
+fn setup_command_line(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "/",
+            TextStyle {
+                font: asset_server.load("fonts/RobotoMono-Light.ttf"),
+                font_size: 20.0,
+                color: Color::Srgba(css::BEIGE),
+            },
+        ).with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.),
+            left: Val::Px(10.),
+            ..default()
+        }).with_background_color(Color::srgba(0., 0., 0., 0.6)),
+        CommandLineText,
+    ));
+}
+
+fn type_command(
+    mut keyboard: EventReader<KeyboardInput>,
+    mut text_query: Query<&mut Text, With<CommandLineText>>,
+    mut command_submitted: EventWriter<CommandSubmitted>,
+    game_ui_state: Res<State<GameUiState>>,
+    mut next_ui_state: ResMut<NextState<GameUiState>>,
+) {
+    // The events are read every frame so the key that opened the command line isn't typed in it
+    if **game_ui_state != GameUiState::CommandLine {
+        keyboard.clear();
+        return;
+    }
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+    let line = &mut text.sections[0].value;
+    for input in keyboard.read() {
+        if input.state != ButtonState::Pressed {
+            continue;
+        }
+        match &input.logical_key {
+            Key::Character(chars) => line.push_str(chars),
+            Key::Space => line.push(' '),
+            Key::Backspace if line.len() > 1 => { line.pop(); },
+            Key::Enter => {
+                command_submitted.send(CommandSubmitted(line.trim_start_matches('/').trim().to_string()));
+                next_ui_state.set(GameUiState::None);
+                return;
+            },
+            _ => {}
+        }
+    }
+}
@@ -2,7 +2,6 @@ use bevy::color::palettes::css;
 use bevy::prelude::*;
 use leafwing_input_manager::prelude::ActionState;
 use crate::Block;
-use crate::world::VoxelWorld;
 use crate::agents::{Dir, TargetBlock};
 
 pub struct DebugDisplayPlugin;
@@ -58,17 +57,18 @@ fn debug_display(
     mut text_query: Query<&mut Text, With<DebugText>>, 
     player_query: Query<(&Transform, &TargetBlock), With<ActionState<Dir>>>,
     ent_query: Query<Entity, With<Transform>>,
-    world: Res<VoxelWorld>,
 ) {
     let (transform, target_block) = player_query.single();
     let mut text = text_query.single_mut();
     text.sections[0].value = format!("p: {:.1}; {:.1}; {:.1}\n", transform.translation.x, transform.translation.y, transform.translation.z);
-    let block = if let Some(raycast_hit) = &target_block.0 {
-        world.get_block_safe(raycast_hit.pos)
+    text.sections[1].value = if let Some(hit) = &target_block.0 {
+        format!(
+            "block: {:?} ({:?} face, {:.1}; {:.1}; {:.1}, {:.1}m)\n", 
+            hit.block, hit.face, hit.point.x, hit.point.y, hit.point.z, hit.dist
+        )
     } else {
-        Block::Air
+        format!("block: {:?}\n", Block::Air)
     };
-    text.sections[1].value = format!("block: {block:?}\n");
     let ent_count = ent_query.iter().count();
     text.sections[2].value = format!("E: {ent_count}\n");
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 use bevy::{color::palettes::css, prelude::*};
+use crate::world::WorldClock;
 
 use super::GameUiState;
 
@@ -7,8 +8,8 @@ pub struct MenuPlugin;
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app 
-            .add_systems(OnEnter(GameUiState::InGameMenu), setup_pause)
-            .add_systems(OnExit(GameUiState::InGameMenu), despawn_screen::<OnPauseScreen>)
+            .add_systems(OnEnter(GameUiState::InGameMenu), (setup_pause, pause_clock))
+            .add_systems(OnExit(GameUiState::InGameMenu), (despawn_screen::<OnPauseScreen>, resume_clock))
             ;
     }
 }
@@ -46,6 +47,14 @@ pub fn setup_pause(mut commands: Commands, asset_server: Res<AssetServer>) {
     });
 }
 
+fn pause_clock(mut clock: ResMut<WorldClock>) {
+    clock.paused = true;
+}
+
+fn resume_clock(mut clock: ResMut<WorldClock>) {
+    clock.paused = false;
+}
+
 // Generic system that takes a component as a parameter, and will despawn all entities with that component
 pub fn despawn_screen<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
     for entity in &to_despawn {
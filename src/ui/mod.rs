@@ -7,7 +7,10 @@ mod crosshair;
 mod in_hand;
 mod furnace_menu;
 mod item_slots;
+mod command_line;
 pub use item_slots::*;
+pub use command_line::{CommandOutput, CommandSubmitted};
+use command_line::CommandLinePlugin;
 use craft_menu::CraftMenuPlugin;
 use furnace_menu::FurnaceMenuPlugin;
 pub use furnace_menu::OpenFurnace;
@@ -39,6 +42,7 @@ impl Plugin for UIPlugin {
             .add_plugins(CraftMenuPlugin)
             .add_plugins(InHandPlugin)
             .add_plugins(FurnaceMenuPlugin)
+            .add_plugins(CommandLinePlugin)
             .add_systems(Startup, setup_ui_actions)
             .add_systems(Startup, setup_crosshair)
             .add_systems(Update, process_ui_actions)
@@ -55,7 +59,8 @@ pub enum GameUiState {
     None,
     InGameMenu,
     CraftingMenu,
-    FurnaceMenu
+    FurnaceMenu,
+    CommandLine,
 }
 
 impl GameUiState {
@@ -71,8 +76,8 @@ impl ComputedStates for ControllingPlayer {
     type SourceStates = GameUiState;
 
     fn compute(sources: Self::SourceStates) -> Option<Self> {
-        // For now ControllingPlayer == CursorGrabbed, but conceptually we could be CursorGrabbed and not controlling player
-        if sources.needs_free_cursor() {
+        // The cursor stays grabbed while typing a command, but the player shouldn't move
+        if sources.needs_free_cursor() || sources == GameUiState::CommandLine {
             None
         } else {
             Some(Self)
@@ -116,12 +121,14 @@ pub enum UIAction {
     CraftingMenu,
     ScrollUp, 
     ScrollDown,
+    CommandLine,
 }
 
 fn setup_ui_actions(mut commands: Commands) {
     let mut input_map = InputMap::new([
         (UIAction::Escape, KeyCode::Escape),
         (UIAction::CraftingMenu, KeyCode::KeyC),
+        (UIAction::CommandLine, KeyCode::Slash),
     ]);
     input_map.insert(UIAction::ScrollUp, MouseScrollDirection::UP);
     input_map.insert(UIAction::ScrollDown, MouseScrollDirection::DOWN);
@@ -144,6 +151,11 @@ fn process_ui_actions(
             } else {
                 next_ui_state.set(GameUiState::None);
             }
+        } else if **game_ui_state == GameUiState::CommandLine {
+            // keys are being typed in the command line
+            continue;
+        } else if action == UIAction::CommandLine && **game_ui_state == GameUiState::None {
+            next_ui_state.set(GameUiState::CommandLine);
         } else if action == UIAction::CraftingMenu && **game_ui_state != GameUiState::InGameMenu {
             if **game_ui_state == GameUiState::None {
                 next_ui_state.set(GameUiState::CraftingMenu);
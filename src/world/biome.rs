@@ -0,0 +1,116 @@
+use std::{fs, ops::Range};
+use bevy::prelude::Resource;
+use riverbed_closest::ClosestTrait;
+use serde::Deserialize;
+use crate::{gen::Tree, Block};
+use super::{BlockPos2d, ColPos, ColedPos, VoxelWorld, CHUNK_S1, CHUNK_S2};
+
+pub const BIOMES_FILE: &str = "assets/gen/biomes.json5";
+
+/// Index of a biome in the biome file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BiomeId(pub u8);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Biome {
+    pub name: String,
+    /// The biome is picked where the climate is the closest to these ranges, values go from 0 to 1
+    pub temperature: (f32, f32),
+    pub humidity: (f32, f32),
+    /// Top block of the ground, picked from the climate in soils_condition.csv if missing
+    pub soil: Option<Block>,
+    /// Blocks under the soil, the soil if missing
+    pub filler: Option<Block>,
+    /// Trees that can grow in the biome, the climate decides which one
+    pub trees: Vec<Tree>,
+    /// Plants of plants_condition.csv that can grow in the biome
+    #[serde(default)]
+    pub plants: Vec<String>,
+    pub grass_tint: [u8; 3],
+    /// Ambient sounds played in the biome
+    #[serde(default)]
+    pub sounds: Vec<String>,
+}
+
+/// The biomes of the Overworld
+#[derive(Resource, Debug, Clone)]
+pub struct Biomes {
+    biomes: Vec<Biome>,
+    climates: Vec<([Range<f32>; 2], BiomeId)>,
+}
+
+impl Biomes {
+    pub fn from_file(path: &str) -> Self {
+        let biomes: Vec<Biome> = json5::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        let climates = biomes.iter().enumerate().map(|(i, biome)| (
+            [biome.temperature.0..biome.temperature.1, biome.humidity.0..biome.humidity.1],
+            BiomeId(i as u8),
+        )).collect();
+        Biomes { biomes, climates }
+    }
+
+    pub fn get(&self, id: BiomeId) -> &Biome {
+        &self.biomes[id.0 as usize]
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<BiomeId> {
+        self.biomes.iter().position(|biome| biome.name == name).map(|i| BiomeId(i as u8))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BiomeId, &Biome)> {
+        self.biomes.iter().enumerate().map(|(i, biome)| (BiomeId(i as u8), biome))
+    }
+
+    /// The biome whose climate ranges are the closest to this climate
+    pub fn from_climate(&self, temperature: f32, humidity: f32) -> BiomeId {
+        *self.climates.closest([temperature, humidity]).0
+    }
+}
+
+/// Biome of each position of a column
+#[derive(Debug, Clone)]
+pub struct ColBiomes(Vec<BiomeId>);
+
+impl ColBiomes {
+    pub fn new(biomes: Vec<BiomeId>) -> Self {
+        assert_eq!(biomes.len(), CHUNK_S2);
+        ColBiomes(biomes)
+    }
+}
+
+pub fn biome_index((x, z): ColedPos) -> usize {
+    x * CHUNK_S1 + z
+}
+
+impl VoxelWorld {
+    /// The biome at pos, None if the column isn't loaded or its realm doesn't have biomes
+    pub fn biome_at(&self, pos: BlockPos2d) -> Option<BiomeId> {
+        let (col_pos, coled_pos) = pos.into();
+        self.biomes.get(&col_pos).map(|biomes| biomes.0[biome_index(coled_pos)])
+    }
+
+    pub fn set_col_biomes(&self, col_pos: ColPos, biomes: ColBiomes) {
+        self.biomes.insert(col_pos, biomes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::{BlockPos2d, ColPos, Realm, VoxelWorld, CHUNK_S2};
+    use super::{BiomeId, Biomes, ColBiomes, BIOMES_FILE};
+
+    #[test]
+    fn test_biomes() {
+        let biomes = Biomes::from_file(BIOMES_FILE);
+        let glacier = biomes.by_name("Glacier").unwrap();
+        assert_eq!(biomes.from_climate(0.05, 0.5), glacier);
+        let world = VoxelWorld::new();
+        let col = ColPos { x: -1, z: 0, realm: Realm::Overworld };
+        let pos = BlockPos2d { x: -1, z: 3, realm: Realm::Overworld };
+        assert_eq!(world.biome_at(pos), None);
+        world.set_col_biomes(col, ColBiomes::new(vec![BiomeId(0); CHUNK_S2]));
+        assert_eq!(world.biome_at(pos), Some(BiomeId(0)));
+        world.unload_col(col);
+        assert_eq!(world.biome_at(pos), None);
+    }
+}
@@ -0,0 +1,39 @@
+use bevy::prelude::{Event, EventWriter, Res};
+use crate::Block;
+use super::{BlockPos, VoxelWorld};
+
+/// What caused a block change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockChangeCause {
+    /// Terrain generation, these changes are not published since the column is new anyway
+    Gen,
+    Player,
+    /// Blocks that change on their own, like furnaces turning on and off
+    Machine,
+    /// Scheduled changes, like depleted blocks renewing
+    Tick,
+    /// Bulk edits made with commands
+    Edit,
+    /// Edits being undone or redone
+    Undo,
+    /// Water flowing
+    Fluid,
+    /// Portals built for travellers arriving in a realm
+    Portal,
+}
+
+/// Published for every block of the VoxelWorld that changed, whoever changed it
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockChanged {
+    pub pos: BlockPos,
+    pub old_block: Block,
+    pub new_block: Block,
+    pub cause: BlockChangeCause,
+}
+
+/// VoxelWorld can be edited from any thread, changes are queued and sent as BlockChanged events every frame
+pub fn send_block_changes(world: Res<VoxelWorld>, mut block_changed: EventWriter<BlockChanged>) {
+    while let Some(change) = world.changes.pop() {
+        block_changed.send(change);
+    }
+}
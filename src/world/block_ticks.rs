@@ -0,0 +1,187 @@
+use std::collections::{BTreeSet, HashMap};
+use bevy::prelude::{Event, EventReader, EventWriter, Res, ResMut, Resource};
+use serde::{Deserialize, Serialize};
+use crate::BlockStateKind;
+use super::{BlockChanged, BlockPos, ColLoadEvent, ColPos, VoxelWorld, WorldClock};
+
+/// Time between two growth stages of a crop
+const GROWTH_MINUTES: u32 = 5;
+
+/// What a scheduled tick does to its block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockTick {
+    /// A depleted block renews
+    Renew,
+    /// A lit furnace burns its fuel and fires its content
+    Furnace,
+    /// A crop grows to its next stage
+    Growth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledTick {
+    pub pos: BlockPos,
+    pub due: u64,
+    pub tick: BlockTick,
+}
+
+/// Sent when a scheduled tick is due. `due` is in the past when a column catches up after being loaded again,
+/// ticks that repeat should be rescheduled from `due` rather than from the current tick so they catch up too.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct BlockTicked {
+    pub pos: BlockPos,
+    pub tick: BlockTick,
+    pub due: u64,
+}
+
+/// Block ticks scheduled for a game tick, there's at most one per position.
+/// Ticks of unloaded columns are kept until their column is loaded again.
+#[derive(Resource, Default)]
+pub struct ScheduledTicks {
+    ticks: HashMap<BlockPos, (u64, BlockTick)>,
+    queue: BTreeSet<(u64, BlockPos)>,
+    // due ticks of unloaded columns
+    waiting: HashMap<ColPos, Vec<BlockPos>>,
+}
+
+impl ScheduledTicks {
+    /// Schedules a tick at pos for the game tick `due`, replacing the one that was scheduled there
+    pub fn schedule(&mut self, pos: BlockPos, due: u64, tick: BlockTick) {
+        if let Some((old_due, _)) = self.ticks.insert(pos, (due, tick)) {
+            self.queue.remove(&(old_due, pos));
+        }
+        self.queue.insert((due, pos));
+    }
+
+    pub fn cancel(&mut self, pos: BlockPos) {
+        if let Some((due, _)) = self.ticks.remove(&pos) {
+            self.queue.remove(&(due, pos));
+        }
+    }
+
+    pub fn get(&self, pos: BlockPos) -> Option<BlockTick> {
+        self.ticks.get(&pos).map(|(_, tick)| *tick)
+    }
+
+    /// Removes the ticks that are due at `now` and returns those of loaded columns
+    pub fn pop_due(&mut self, world: &VoxelWorld, now: u64) -> Vec<BlockTicked> {
+        let mut due_ticks = Vec::new();
+        while let Some((due, pos)) = self.queue.first().copied() {
+            if due > now {
+                break;
+            }
+            self.queue.pop_first();
+            if !world.is_loaded(pos) {
+                self.waiting.entry(ColPos::from(pos)).or_default().push(pos);
+                continue;
+            }
+            if let Some((due, tick)) = self.ticks.remove(&pos) {
+                due_ticks.push(BlockTicked { pos, tick, due });
+            }
+        }
+        due_ticks
+    }
+
+    /// The ticks of the column that were due while it was unloaded are queued again
+    pub fn on_col_load(&mut self, col_pos: ColPos) {
+        for pos in self.waiting.remove(&col_pos).unwrap_or_default() {
+            if let Some((due, _)) = self.ticks.get(&pos) {
+                self.queue.insert((*due, pos));
+            }
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<ScheduledTick> {
+        self.ticks.iter().map(|(pos, (due, tick))| ScheduledTick { pos: *pos, due: *due, tick: *tick }).collect()
+    }
+}
+
+impl From<Vec<ScheduledTick>> for ScheduledTicks {
+    fn from(scheduled: Vec<ScheduledTick>) -> Self {
+        let mut ticks = ScheduledTicks::default();
+        for ScheduledTick { pos, due, tick } in scheduled {
+            ticks.schedule(pos, due, tick);
+        }
+        ticks
+    }
+}
+
+pub fn run_block_ticks(
+    world: Res<VoxelWorld>,
+    clock: Res<WorldClock>,
+    mut ticks: ResMut<ScheduledTicks>,
+    mut ev_ticked: EventWriter<BlockTicked>,
+) {
+    ev_ticked.send_batch(ticks.pop_due(&world, clock.tick));
+}
+
+pub fn requeue_loaded_ticks(mut ticks: ResMut<ScheduledTicks>, mut ev_load: EventReader<ColLoadEvent>) {
+    for ColLoadEvent(col_pos) in ev_load.read() {
+        ticks.on_col_load(*col_pos);
+    }
+}
+
+/// Crops that are placed start growing
+pub fn schedule_growth(
+    clock: Res<WorldClock>,
+    mut ticks: ResMut<ScheduledTicks>,
+    mut block_changes: EventReader<BlockChanged>,
+) {
+    for change in block_changes.read() {
+        if matches!(change.new_block.state_kind(), Some(BlockStateKind::Growth(_))) {
+            ticks.schedule(change.pos, clock.tick + WorldClock::minutes(GROWTH_MINUTES), BlockTick::Growth);
+        }
+    }
+}
+
+pub fn grow_crops(world: Res<VoxelWorld>, mut ticks: ResMut<ScheduledTicks>, mut ev_ticked: EventReader<BlockTicked>) {
+    for ticked in ev_ticked.read() {
+        if ticked.tick != BlockTick::Growth {
+            continue;
+        }
+        let stage = world.get_state(ticked.pos) + 1;
+        if stage >= world.get_block(ticked.pos).state_count() {
+            continue;
+        }
+        world.set_state(ticked.pos, stage);
+        if stage + 1 < world.get_block(ticked.pos).state_count() {
+            ticks.schedule(ticked.pos, ticked.due + WorldClock::minutes(GROWTH_MINUTES), BlockTick::Growth);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{world::{BlockChangeCause, BlockPos, ColPos, Realm, VoxelWorld}, Block};
+    use super::{BlockTick, ScheduledTicks};
+
+    #[test]
+    fn test_ticks_wait_for_their_column() {
+        let world = VoxelWorld::new();
+        let mut ticks = ScheduledTicks::default();
+        let loaded = BlockPos { x: 0, y: 0, z: 0, realm: Realm::Overworld };
+        let unloaded = BlockPos { x: 100, y: 0, z: 0, realm: Realm::Overworld };
+        // truncating x would give column 0, which is loaded
+        let negative = BlockPos { x: -5, y: 0, z: 0, realm: Realm::Overworld };
+        world.set_block(loaded, Block::Granite, BlockChangeCause::Gen);
+        ticks.schedule(loaded, 10, BlockTick::Renew);
+        ticks.schedule(loaded, 20, BlockTick::Renew);
+        ticks.schedule(unloaded, 5, BlockTick::Renew);
+        ticks.schedule(negative, 6, BlockTick::Renew);
+        assert!(ticks.pop_due(&world, 15).is_empty());
+        let due = ticks.pop_due(&world, 20);
+        assert_eq!(due.len(), 1);
+        assert_eq!((due[0].pos, due[0].due), (loaded, 20));
+        // the column of the other tick is loaded, it catches up
+        world.set_block(unloaded, Block::Granite, BlockChangeCause::Gen);
+        ticks.on_col_load(ColPos { x: 1, z: 0, realm: Realm::Overworld });
+        let due = ticks.pop_due(&world, 30);
+        assert_eq!(due.len(), 1);
+        assert_eq!((due[0].pos, due[0].due), (unloaded, 5));
+        world.set_block(negative, Block::Granite, BlockChangeCause::Gen);
+        ticks.on_col_load(ColPos { x: -1, z: 0, realm: Realm::Overworld });
+        let due = ticks.pop_due(&world, 30);
+        assert_eq!((due[0].pos, due[0].due), (negative, 6));
+        assert!(ticks.to_vec().is_empty());
+    }
+}
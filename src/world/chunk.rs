@@ -1,12 +1,18 @@
-use itertools::Itertools;
+use itertools::{iproduct, Itertools};
 use packed_uints::PackedUints;
-use crate::Block;
-use super::{pos::{ChunkedPos, ColedPos}, utils::Palette, CHUNKP_S1, CHUNKP_S2, CHUNKP_S3, CHUNK_S1};
+use crate::{block::BlockState, Block};
+use super::{pos::{ChunkedPos, ColedPos}, utils::{read_varint, write_varint, Palette}, CHUNKP_S1, CHUNKP_S2, CHUNKP_S3, CHUNK_S1};
+/// Bump this when the encoded format changes, older versions must stay decodable
+const CHUNK_CODEC_VERSION: u8 = 2;
 
+/// Chunks made of a single block (in its default state) don't allocate any voxel data
 #[derive(Debug)]
-pub struct Chunk {
-    pub data: PackedUints,
-    pub palette: Palette<Block>,
+pub enum Chunk {
+    Uniform(Block),
+    Paletted {
+        data: PackedUints,
+        palette: Palette<(Block, BlockState)>,
+    }
 }
 
 pub fn linearize(x: usize, y: usize, z: usize) -> usize {
@@ -17,20 +23,64 @@ pub fn pad_linearize(x: usize, y: usize, z: usize) -> usize {
     z + 1 + (x+1) * CHUNKP_S1 + (y+1) * CHUNKP_S2
 }
 
+// Palette sizes at which PackedUints needs a wider bit width
+const COMPACT_THRESHOLDS: [usize; 2] = [16, 256];
+
 impl Chunk {
     pub fn get(&self, (x, y, z): ChunkedPos) -> &Block {
-        &self.palette[self.data.get(pad_linearize(x, y, z))]
+        match self {
+            Chunk::Uniform(block) => block,
+            Chunk::Paletted { data, palette } => &palette[data.get(pad_linearize(x, y, z))].0
+        }
     }
 
-    pub fn set(&mut self, (x, y, z): ChunkedPos, block: Block) {
+    pub fn get_state(&self, (x, y, z): ChunkedPos) -> BlockState {
+        match self {
+            Chunk::Uniform(_) => 0,
+            Chunk::Paletted { data, palette } => palette[data.get(pad_linearize(x, y, z))].1
+        }
+    }
+
+    /// Returns the paletted data and the palette index of (block, state), 
+    /// or None if the chunk is uniformly made of block and there's nothing to do
+    fn paletted_index(&mut self, block: Block, state: BlockState) -> Option<(&mut PackedUints, usize)> {
+        // compact right before a new block would make data use a wider bit width
+        if let Chunk::Paletted { palette, .. } = self {
+            if !palette.contains(&(block, state)) && COMPACT_THRESHOLDS.contains(&palette.len()) {
+                self.compact();
+            }
+        }
+        if let Chunk::Uniform(uniform) = self {
+            if *uniform == block && state == 0 {
+                return None;
+            }
+            *self = Chunk::paletted(*uniform);
+        }
+        let Chunk::Paletted { data, palette } = self else {
+            unreachable!()
+        };
+        let value = palette.index((block, state));
+        Some((data, value))
+    }
+
+    /// Sets the block in its default state
+    pub fn set(&mut self, pos: ChunkedPos, block: Block) {
+        self.set_with_state(pos, block, 0);
+    }
+
+    pub fn set_with_state(&mut self, (x, y, z): ChunkedPos, block: Block, state: BlockState) {
         let idx = pad_linearize(x, y, z);
-        self.data.set(idx, self.palette.index(block));
+        if let Some((data, value)) = self.paletted_index(block, state) {
+            data.set(idx, value);
+        }
     }
 
     pub fn set_yrange(&mut self, (x, top, z): ChunkedPos, height: usize, block: Block) {
-        let value = self.palette.index(block);
+        let Some((data, value)) = self.paletted_index(block, 0) else {
+            return;
+        };
         // Note: we do end+1 because set_range(_step) is not inclusive
-        self.data.set_range_step(
+        data.set_range_step(
             pad_linearize(x, top - height, z), 
             pad_linearize(x, top, z)+1, 
             CHUNKP_S2,
@@ -41,50 +91,271 @@ impl Chunk {
     // Used for efficient construction of mesh data
     pub fn copy_column(&self, buffer: &mut [Block], (x, z): ColedPos, lod: usize) {
         let start = pad_linearize(x, 0, z);
-        let mut i = 0;
-        for idx in (start..(start+CHUNK_S1)).step_by(lod) {
-            buffer[i] = self.palette[self.data.get(idx)];
-            i += 1;
+        for (i, idx) in (start..(start+CHUNK_S1)).step_by(lod).enumerate() {
+            buffer[i] = match self {
+                Chunk::Uniform(block) => *block,
+                Chunk::Paletted { data, palette } => palette[data.get(idx)].0
+            };
         }
     }
 
-    pub fn top(&self, (x, z): ColedPos) -> (&Block, usize) {
-        for y in (0..CHUNK_S1).rev() {
-            let b_idx = self.data.get(pad_linearize(x, y, z));
-            if b_idx > 0 {
-                return (&self.palette[b_idx], y);
-            }
+    pub fn set_if_empty(&mut self, pos: ChunkedPos, block: Block) -> bool {
+        if *self.get(pos) != Block::Air {
+            return false;
         }
-        (&self.palette[0], 0)
+        self.set(pos, block);
+        true
     }
 
-    pub fn set_if_empty(&mut self, (x, y, z): ChunkedPos, block: Block) -> bool {
-        let idx = pad_linearize(x, y, z);
-        if self.palette[self.data.get(idx)] != Block::Air {
-            return false;
+    /// The blocks of the palette with their state, index 0 is always Air
+    pub fn palette_blocks(&self) -> Vec<(Block, BlockState)> {
+        match self {
+            Chunk::Uniform(Block::Air) => vec![(Block::Air, 0)],
+            Chunk::Uniform(block) => vec![(Block::Air, 0), (*block, 0)],
+            Chunk::Paletted { palette, .. } => palette.iter().copied().collect()
         }
-        self.data.set(idx, self.palette.index(block));
-        true
+    }
+
+    /// Padded palette indices of the chunk, consistent with palette_blocks
+    pub fn voxels(&self) -> Vec<u16> {
+        match self {
+            Chunk::Uniform(Block::Air) => vec![0; CHUNKP_S3],
+            Chunk::Uniform(_) => {
+                let mut voxels = vec![0; CHUNKP_S3];
+                for (y, x, z) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1, 0..CHUNK_S1) {
+                    voxels[pad_linearize(x, y, z)] = 1;
+                }
+                voxels
+            },
+            Chunk::Paletted { data, .. } => data.unpack_u16()
+        }
+    }
+
+    /// Removes unused palette entries (shrinking the bit width of data if possible), 
+    /// and turns the chunk uniform if it only contains 1 block in its default state
+    pub fn compact(&mut self) {
+        let Chunk::Paletted { data, palette } = self else {
+            return;
+        };
+        let voxels = data.unpack_u16();
+        let mut used = vec![false; palette.len()];
+        // padding is not taken into account, it's not part of the chunk
+        for (y, x, z) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1, 0..CHUNK_S1) {
+            used[voxels[pad_linearize(x, y, z)] as usize] = true;
+        }
+        let mut used_blocks = palette.iter().zip(used.iter()).filter(|(_, used)| **used);
+        if let (Some(((block, 0), _)), None) = (used_blocks.next(), used_blocks.next()) {
+            *self = Chunk::Uniform(*block);
+            return;
+        }
+        // Air must stay at index 0 so we don't care if it's unused
+        if used.iter().skip(1).all(|used| *used) {
+            return;
+        }
+        let mut new_palette = Palette::new();
+        new_palette.index((Block::Air, 0));
+        let remap = palette.iter().zip(used).map(|(entry, used)| 
+            if used { new_palette.index(*entry) } else { 0 }
+        ).collect_vec();
+        *data = PackedUints::from(voxels.iter().map(|v| remap[*v as usize]).collect_vec().as_slice());
+        *palette = new_palette;
+    }
+
+    fn paletted(block: Block) -> Self {
+        let mut palette = Palette::new();
+        palette.index((Block::Air, 0));
+        let value = palette.index((block, 0));
+        let data = if value == 0 {
+            PackedUints::new(CHUNKP_S3)
+        } else {
+            let mut values = vec![0; CHUNKP_S3];
+            for (y, x, z) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1, 0..CHUNK_S1) {
+                values[pad_linearize(x, y, z)] = value;
+            }
+            PackedUints::from(values.as_slice())
+        };
+        Chunk::Paletted { data, palette }
     }
 }
 
 impl From<&[Block]> for Chunk {
     fn from(values: &[Block]) -> Self {
         let mut palette = Palette::new();
-        palette.index(Block::Air);
-        let values = values.iter().map(|v| palette.index(v.clone())).collect_vec();
+        palette.index((Block::Air, 0));
+        let values = values.iter().map(|v| palette.index((*v, 0))).collect_vec();
         let data = PackedUints::from(values.as_slice());
-        Chunk {data, palette}
+        Chunk::Paletted {data, palette}
     }
 }
 
 impl Chunk {
     pub fn new() -> Self {
+        Chunk::Uniform(Block::Air)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChunkDecodeError {
+    UnknownVersion(u8),
+    Truncated,
+    BadPaletteIndex(usize),
+    WrongVoxelCount(usize),
+}
+
+impl Chunk {
+    /// Encodes the unpadded interior of the chunk:
+    /// `version: u8 | palette length | (block id, state)* | (run length, palette index)*`, all as varints after the version.
+    /// Voxels are visited in (y, x, z) order so runs follow the memory layout.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![CHUNK_CODEC_VERSION];
+        let palette = self.palette_blocks();
+        write_varint(&mut bytes, palette.len());
+        for (block, state) in palette {
+            write_varint(&mut bytes, block.id() as usize);
+            write_varint(&mut bytes, state as usize);
+        }
+        let voxels = self.voxels();
+        let mut run: Option<(u16, usize)> = None;
+        for (y, x, z) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1, 0..CHUNK_S1) {
+            let value = voxels[pad_linearize(x, y, z)];
+            run = match run {
+                Some((current, len)) if current == value => Some((current, len+1)),
+                Some((current, len)) => {
+                    write_varint(&mut bytes, len);
+                    write_varint(&mut bytes, current as usize);
+                    Some((value, 1))
+                },
+                None => Some((value, 1))
+            };
+        }
+        if let Some((current, len)) = run {
+            write_varint(&mut bytes, len);
+            write_varint(&mut bytes, current as usize);
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ChunkDecodeError> {
+        let (version, mut reader) = bytes.split_first().ok_or(ChunkDecodeError::Truncated)?;
+        // version 1 didn't store block states
+        if *version == 0 || *version > CHUNK_CODEC_VERSION {
+            return Err(ChunkDecodeError::UnknownVersion(*version));
+        }
         let mut palette = Palette::new();
-        palette.index(Block::Air); 
-        Chunk {
-            data: PackedUints::new(CHUNKP_S3),
-            palette: palette, 
+        palette.index((Block::Air, 0));
+        let mut remap = Vec::new();
+        for _ in 0..read_varint(&mut reader).ok_or(ChunkDecodeError::Truncated)? {
+            // blocks that don't exist anymore are replaced by air
+            let id = read_varint(&mut reader).ok_or(ChunkDecodeError::Truncated)?;
+            let block = u16::try_from(id).ok().and_then(Block::from_id).unwrap_or(Block::Air);
+            let state = if *version >= 2 {
+                read_varint(&mut reader).ok_or(ChunkDecodeError::Truncated)?
+            } else {
+                0
+            };
+            // states that the block doesn't have anymore are reset
+            let state = if state < block.state_count() as usize { state as BlockState } else { 0 };
+            remap.push(palette.index((block, state)));
+        }
+        let mut values = vec![0; CHUNKP_S3];
+        let mut voxels = iproduct!(0..CHUNK_S1, 0..CHUNK_S1, 0..CHUNK_S1);
+        let mut count: usize = 0;
+        while !reader.is_empty() {
+            let len = read_varint(&mut reader).ok_or(ChunkDecodeError::Truncated)?;
+            let palette_i = read_varint(&mut reader).ok_or(ChunkDecodeError::Truncated)?;
+            let value = *remap.get(palette_i).ok_or(ChunkDecodeError::BadPaletteIndex(palette_i))?;
+            // the run length is untrusted, a corrupt file must not overflow the count
+            count = count.saturating_add(len);
+            if count > CHUNK_S1.pow(3) {
+                return Err(ChunkDecodeError::WrongVoxelCount(count));
+            }
+            for (y, x, z) in voxels.by_ref().take(len) {
+                values[pad_linearize(x, y, z)] = value;
+            }
+        }
+        if count != CHUNK_S1.pow(3) {
+            return Err(ChunkDecodeError::WrongVoxelCount(count));
+        }
+        // PackedUints can't be built from zeros only, it happens when none of the blocks exist anymore
+        if values.iter().all(|value| *value == 0) {
+            return Ok(Chunk::new());
         }
+        let mut chunk = Chunk::Paletted {
+            data: PackedUints::from(values.as_slice()),
+            palette,
+        };
+        chunk.compact();
+        Ok(chunk)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Block;
+    use super::{write_varint, Chunk, ChunkDecodeError, CHUNK_CODEC_VERSION, CHUNK_S1};
+
+    #[test]
+    fn test_cleared_chunk_becomes_uniform() {
+        let mut chunk = Chunk::new();
+        chunk.set((1, 2, 3), Block::OakLog);
+        chunk.set((1, 3, 3), Block::OakLeaves);
+        assert!(matches!(chunk, Chunk::Paletted { .. }));
+        chunk.set((1, 2, 3), Block::Air);
+        chunk.set((1, 3, 3), Block::Air);
+        chunk.compact();
+        assert!(matches!(chunk, Chunk::Uniform(Block::Air)));
+    }
+
+    #[test]
+    fn test_compact_keeps_blocks() {
+        let mut chunk = Chunk::new();
+        chunk.set_yrange((0, 10, 0), 10, Block::Granite);
+        chunk.set((5, 5, 5), Block::OakLog);
+        chunk.set((6, 5, 5), Block::Dirt);
+        chunk.set((5, 5, 5), Block::Air);
+        chunk.compact();
+        let Chunk::Paletted { palette, .. } = &chunk else {
+            panic!("chunk shouldn't be uniform");
+        };
+        assert_eq!(palette.len(), 3);
+        assert_eq!(*chunk.get((0, 3, 0)), Block::Granite);
+        assert_eq!(*chunk.get((6, 5, 5)), Block::Dirt);
+        assert_eq!(*chunk.get((5, 5, 5)), Block::Air);
+    }
+
+    #[test]
+    fn test_state_roundtrip() {
+        let mut chunk = Chunk::new();
+        chunk.set_with_state((1, 2, 3), Block::OakLog, 2);
+        chunk.set((1, 3, 3), Block::OakLog);
+        let decoded = Chunk::decode(&chunk.encode()).unwrap();
+        assert_eq!(*decoded.get((1, 2, 3)), Block::OakLog);
+        assert_eq!(decoded.get_state((1, 2, 3)), 2);
+        assert_eq!(decoded.get_state((1, 3, 3)), 0);
+    }
+
+    #[test]
+    fn test_uniform_roundtrip() {
+        let decoded = Chunk::decode(&Chunk::Uniform(Block::Granite).encode()).unwrap();
+        assert!(matches!(decoded, Chunk::Uniform(Block::Granite)));
+    }
+
+    #[test]
+    fn test_decode_rejects_long_runs() {
+        let mut bytes = Chunk::Uniform(Block::Granite).encode();
+        // a corrupt region file with an extra run that would overflow the voxel count
+        write_varint(&mut bytes, usize::MAX / 2);
+        write_varint(&mut bytes, 0);
+        assert!(matches!(Chunk::decode(&bytes), Err(ChunkDecodeError::WrongVoxelCount(_))));
+    }
+
+    #[test]
+    fn test_unknown_ids_become_air() {
+        // one palette entry with an id that doesn't fit in a u16, and its state, then a run filling the chunk
+        let mut bytes = vec![CHUNK_CODEC_VERSION];
+        for value in [1, (1 << 16) + Block::Granite.id() as usize, 0, CHUNK_S1.pow(3), 0] {
+            write_varint(&mut bytes, value);
+        }
+        assert!(matches!(Chunk::decode(&bytes).unwrap(), Chunk::Uniform(Block::Air)));
+    }
+}
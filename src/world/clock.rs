@@ -0,0 +1,39 @@
+use bevy::prelude::{Res, ResMut, Resource, Time};
+
+pub const TICKS_PER_SEC: u64 = 20;
+
+/// Game time counted in ticks since the world was created,
+/// it only advances while the game isn't paused and is saved with the world
+#[derive(Resource, Default)]
+pub struct WorldClock {
+    pub tick: u64,
+    pub paused: bool,
+    // fraction of a tick elapsed since the last one
+    partial: f32,
+}
+
+impl WorldClock {
+    pub fn new(tick: u64) -> Self {
+        WorldClock { tick, ..Default::default() }
+    }
+
+    /// Number of ticks in `secs` seconds of game time
+    pub fn secs(secs: u32) -> u64 {
+        secs as u64 * TICKS_PER_SEC
+    }
+
+    /// Number of ticks in `minutes` minutes of game time
+    pub fn minutes(minutes: u32) -> u64 {
+        WorldClock::secs(minutes * 60)
+    }
+}
+
+pub fn advance_clock(time: Res<Time>, mut clock: ResMut<WorldClock>) {
+    if clock.paused {
+        return;
+    }
+    clock.partial += time.delta_seconds() * TICKS_PER_SEC as f32;
+    let ticks = clock.partial.floor();
+    clock.partial -= ticks;
+    clock.tick += ticks as u64;
+}
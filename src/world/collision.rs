@@ -0,0 +1,205 @@
+use bevy::prelude::Vec3;
+use itertools::iproduct;
+use crate::Block;
+use super::{BlockPos, Realm, VoxelWorld};
+
+/// Axis aligned box used for collisions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollisionBox {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl CollisionBox {
+    pub fn new(min: Vec3, size: Vec3) -> Self {
+        CollisionBox { min, max: min + size }
+    }
+
+    pub fn translated(&self, offset: Vec3) -> Self {
+        CollisionBox { min: self.min + offset, max: self.max + offset }
+    }
+
+    /// Boxes that only touch don't overlap
+    pub fn overlaps(&self, other: &CollisionBox) -> bool {
+        self.min.cmplt(other.max).all() && other.min.cmplt(self.max).all()
+    }
+
+    // The positions of the blocks that intersect the box
+    fn block_positions(&self, realm: Realm) -> impl Iterator<Item = BlockPos> {
+        let min = self.min.floor().as_ivec3();
+        let max = self.max.ceil().as_ivec3();
+        iproduct!(min.x..max.x, min.y..max.y, min.z..max.z).map(move |(x, y, z)| BlockPos { x, y, z, realm })
+    }
+}
+
+impl Block {
+    /// Collision box of the block within its unit cube, None if it can be walked through
+    pub fn collision_box(&self) -> Option<CollisionBox> {
+        if self.is_traversable() {
+            return None;
+        }
+        Some(match self {
+            Block::Campfire => CollisionBox { min: Vec3::ZERO, max: Vec3::new(1., 0.5, 1.) },
+            _ => CollisionBox { min: Vec3::ZERO, max: Vec3::ONE },
+        })
+    }
+}
+
+pub struct SweepHit {
+    /// Fraction of the motion done before the hit, between 0 and 1
+    pub t: f32,
+    /// Normal of the face that was hit
+    pub normal: Vec3,
+}
+
+// Allows boxes that are touching (or barely overlapping due to float errors) to still collide
+const SWEEP_EPSILON: f32 = 1e-4;
+
+// Time of entry and normal of a box moving by delta against an obstacle, if it hits it during the motion
+fn sweep_box(moving: &CollisionBox, delta: Vec3, obstacle: &CollisionBox) -> Option<(f32, Vec3)> {
+    let mut t_entry = f32::NEG_INFINITY;
+    let mut t_exit = f32::INFINITY;
+    let mut normal = Vec3::ZERO;
+    for axis in 0..3 {
+        if delta[axis] == 0. {
+            if moving.min[axis] >= obstacle.max[axis] || moving.max[axis] <= obstacle.min[axis] {
+                return None;
+            }
+            continue;
+        }
+        let (entry, exit) = if delta[axis] > 0. {
+            (obstacle.min[axis] - moving.max[axis], obstacle.max[axis] - moving.min[axis])
+        } else {
+            (obstacle.max[axis] - moving.min[axis], obstacle.min[axis] - moving.max[axis])
+        };
+        let (entry, exit) = (entry / delta[axis], exit / delta[axis]);
+        if entry > t_entry {
+            t_entry = entry;
+            normal = Vec3::ZERO;
+            normal[axis] = -delta[axis].signum();
+        }
+        t_exit = t_exit.min(exit);
+    }
+    let epsilon = SWEEP_EPSILON / delta.length();
+    if t_entry < -epsilon || t_entry > 1. || t_entry >= t_exit {
+        return None;
+    }
+    Some((t_entry.max(0.), normal))
+}
+
+// How many times a box pushed out of blocks can land in other blocks before giving up
+const PUSH_OUT_STEPS: usize = 3;
+// Distance kept between a pushed out box and the blocks it was in, so float errors don't put it back in them
+const PUSH_OUT_GAP: f32 = 0.001;
+
+impl VoxelWorld {
+    /// True if a box of `size` with its lowest corner at `min` overlaps the collision box of any block
+    pub fn overlaps_aabb(&self, realm: Realm, min: Vec3, size: Vec3) -> bool {
+        !self.overlapping_boxes(realm, &CollisionBox::new(min, size)).is_empty()
+    }
+
+    // The collision boxes of the blocks that overlap aabb
+    fn overlapping_boxes(&self, realm: Realm, aabb: &CollisionBox) -> Vec<CollisionBox> {
+        aabb.block_positions(realm).filter_map(|pos| self.get_block_safe(pos).collision_box()
+            .map(|block_box| block_box.translated(pos.into()))
+            .filter(|block_box| block_box.overlaps(aabb))
+        ).collect()
+    }
+
+    /// The shortest offset that gets a box of `size` with its lowest corner at `min` out of the blocks it overlaps,
+    /// the box is pushed along one axis at a time. Zero if it doesn't overlap any block, None if it's buried too deep.
+    pub fn push_out_aabb(&self, realm: Realm, min: Vec3, size: Vec3) -> Option<Vec3> {
+        let mut best: Option<Vec3> = None;
+        let mut offsets = vec![Vec3::ZERO];
+        for _ in 0..=PUSH_OUT_STEPS {
+            let mut next_offsets = Vec::new();
+            for offset in offsets {
+                if best.is_some_and(|best| best.length() <= offset.length()) {
+                    continue;
+                }
+                let aabb = CollisionBox::new(min + offset, size);
+                let blocking = self.overlapping_boxes(realm, &aabb);
+                if blocking.is_empty() {
+                    best = Some(offset);
+                    continue;
+                }
+                for axis in 0..3 {
+                    let mut up = Vec3::ZERO;
+                    up[axis] = blocking.iter().map(|block_box| block_box.max[axis] - aabb.min[axis]).fold(0., f32::max) + PUSH_OUT_GAP;
+                    let mut down = Vec3::ZERO;
+                    down[axis] = blocking.iter().map(|block_box| block_box.min[axis] - aabb.max[axis]).fold(0., f32::min) - PUSH_OUT_GAP;
+                    next_offsets.extend([offset + up, offset + down]);
+                }
+            }
+            offsets = next_offsets;
+        }
+        best
+    }
+
+    /// Moves a box of `size` with its lowest corner at `start` by `delta`,
+    /// returns the first block collision box it runs into, if any.
+    /// Blocks the box already overlaps at the start are ignored so it can get out of them.
+    pub fn sweep_aabb(&self, realm: Realm, size: Vec3, start: Vec3, delta: Vec3) -> Option<SweepHit> {
+        if delta == Vec3::ZERO {
+            return None;
+        }
+        let aabb = CollisionBox::new(start, size);
+        let swept = CollisionBox {
+            min: aabb.min.min(aabb.min + delta),
+            max: aabb.max.max(aabb.max + delta)
+        };
+        let mut closest: Option<SweepHit> = None;
+        for pos in swept.block_positions(realm) {
+            let Some(block_box) = self.get_block_safe(pos).collision_box() else {
+                continue;
+            };
+            let Some((t, normal)) = sweep_box(&aabb, delta, &block_box.translated(pos.into())) else {
+                continue;
+            };
+            if closest.as_ref().map_or(true, |hit| t < hit.t) {
+                closest = Some(SweepHit { t, normal });
+            }
+        }
+        closest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Vec3;
+    use crate::{world::{BlockChangeCause, BlockPos, Realm, VoxelWorld}, Block};
+
+    #[test]
+    fn test_sweep_and_overlap() {
+        let world = VoxelWorld::new();
+        let floor = BlockPos { x: 0, y: 10, z: 0, realm: Realm::Overworld };
+        world.set_block(floor, Block::Granite, BlockChangeCause::Gen);
+        let size = Vec3::new(0.5, 1.7, 0.5);
+        let start = Vec3::new(0.2, 13., 0.2);
+        let hit = world.sweep_aabb(Realm::Overworld, size, start, Vec3::new(0., -5., 0.)).unwrap();
+        assert_eq!(hit.normal, Vec3::Y);
+        assert!((hit.t - 0.4).abs() < 1e-5);
+        // resting on the floor is not overlapping it
+        assert!(!world.overlaps_aabb(Realm::Overworld, Vec3::new(0.2, 11., 0.2), size));
+        assert!(world.overlaps_aabb(Realm::Overworld, Vec3::new(0.2, 10.9, 0.2), size));
+        // moving sideways next to the block doesn't hit it
+        assert!(world.sweep_aabb(Realm::Overworld, size, Vec3::new(0.2, 11., 0.2), Vec3::new(3., 0., 0.)).is_none());
+    }
+
+    #[test]
+    fn test_push_out() {
+        let world = VoxelWorld::new();
+        for (x, y) in [(0, 10), (1, 11), (1, 12), (0, 13)] {
+            world.set_block(BlockPos { x, y, z: 0, realm: Realm::Overworld }, Block::Granite, BlockChangeCause::Gen);
+        }
+        let size = Vec3::new(0.5, 1.7, 0.5);
+        assert_eq!(world.push_out_aabb(Realm::Overworld, Vec3::new(0.2, 11., 0.2), size), Some(Vec3::ZERO));
+        // sunk a bit in the floor, it's pushed back on top of it
+        let offset = world.push_out_aabb(Realm::Overworld, Vec3::new(0.2, 10.9, 0.2), size).unwrap();
+        assert!(offset.x == 0. && offset.z == 0. && (offset.y - 0.1).abs() < 0.01);
+        // in the wall of a tunnel with a ceiling, it's pushed back in the tunnel instead of through the ceiling
+        let offset = world.push_out_aabb(Realm::Overworld, Vec3::new(0.6, 11., 0.2), size).unwrap();
+        assert!(offset.y == 0. && offset.z == 0. && (offset.x + 0.1).abs() < 0.01);
+        assert!(!world.overlaps_aabb(Realm::Overworld, Vec3::new(0.6, 11., 0.2) + offset, size));
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::VecDeque;
+use bevy::prelude::{EventReader, ResMut, Resource};
+use crate::Block;
+use super::{BlockChangeCause, BlockChanged, BlockPos, VoxelWorld};
+
+/// Number of edit batches that can be undone
+const MAX_HISTORY: usize = 100;
+
+/// The block changes of a single action (a placed block, a bulk fill, a paste...)
+#[derive(Debug, Clone, Default)]
+pub struct EditBatch(pub Vec<(BlockPos, Block, Block)>);
+
+/// Records the edits made to the world so they can be undone and redone.
+#[derive(Resource, Default)]
+pub struct EditJournal {
+    undos: VecDeque<EditBatch>,
+    redos: Vec<EditBatch>,
+}
+
+impl EditJournal {
+    pub fn record(&mut self, batch: EditBatch) {
+        if batch.0.is_empty() {
+            return;
+        }
+        self.redos.clear();
+        self.undos.push_back(batch);
+        if self.undos.len() > MAX_HISTORY {
+            self.undos.pop_front();
+        }
+    }
+
+    /// Reverts the last batch of edits, returns false if there was nothing to undo
+    pub fn undo(&mut self, world: &VoxelWorld) -> bool {
+        let Some(batch) = self.undos.pop_back() else {
+            return false;
+        };
+        for (pos, old_block, new_block) in batch.0.iter().rev() {
+            revert(world, *pos, *new_block, *old_block);
+        }
+        self.redos.push(batch);
+        true
+    }
+
+    /// Reapplies the last undone batch of edits, returns false if there was nothing to redo
+    pub fn redo(&mut self, world: &VoxelWorld) -> bool {
+        let Some(batch) = self.redos.pop() else {
+            return false;
+        };
+        for (pos, old_block, new_block) in batch.0.iter() {
+            revert(world, *pos, *old_block, *new_block);
+        }
+        self.undos.push_back(batch);
+        true
+    }
+}
+
+// Sets the block at pos from `from` to `to`,
+// blocks that were changed since or aren't loaded anymore are left as is
+fn revert(world: &VoxelWorld, pos: BlockPos, from: Block, to: Block) {
+    if !world.is_loaded(pos) || world.get_block(pos) != from {
+        return;
+    }
+    world.set_block(pos, to, BlockChangeCause::Undo);
+}
+
+fn is_journaled(cause: BlockChangeCause) -> bool {
+    matches!(cause, BlockChangeCause::Player | BlockChangeCause::Edit)
+}
+
+/// The journaled changes of a frame are grouped in a single batch
+pub fn record_edits(mut block_changes: EventReader<BlockChanged>, mut journal: ResMut<EditJournal>) {
+    let batch = block_changes.read()
+        .filter(|change| is_journaled(change.cause))
+        .map(|change| (change.pos, change.old_block, change.new_block))
+        .collect();
+    journal.record(EditBatch(batch));
+}
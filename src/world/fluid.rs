@@ -0,0 +1,180 @@
+use std::collections::{HashSet, VecDeque};
+use bevy::prelude::{EventReader, Res, ResMut, Resource, Time, Timer};
+use crate::Block;
+use super::{BlockChangeCause, BlockChanged, BlockPos, VoxelWorld, MAX_HEIGHT};
+
+/// Water cells updated per fluid tick, so large floods don't freeze the game
+const FLUID_BUDGET: usize = 2000;
+const SIDES: [(i32, i32, i32); 4] = [(-1, 0, 0), (1, 0, 0), (0, 0, -1), (0, 0, 1)];
+
+/// Volume of a full water block, the level state of SeaBlock is the missing volume so generated water is full
+fn max_volume() -> u8 {
+    Block::SeaBlock.state_count()
+}
+
+/// Water volume at pos, 0 for air, None if water can't go there (solid or not loaded)
+fn water_volume(world: &VoxelWorld, pos: BlockPos) -> Option<u8> {
+    if pos.y < 0 || pos.y >= MAX_HEIGHT as i32 || !world.is_loaded(pos) {
+        return None;
+    }
+    match world.get_block(pos) {
+        Block::Air => Some(0),
+        Block::SeaBlock => Some(max_volume() - world.get_state(pos)),
+        _ => None,
+    }
+}
+
+fn set_water_volume(world: &VoxelWorld, pos: BlockPos, volume: u8) {
+    if volume == 0 {
+        world.set_block(pos, Block::Air, BlockChangeCause::Fluid);
+    } else if world.get_block(pos) == Block::SeaBlock {
+        world.set_state(pos, max_volume() - volume);
+    } else {
+        world.set_block_with_state(pos, Block::SeaBlock, max_volume() - volume, BlockChangeCause::Fluid);
+    }
+}
+
+/// Water that may flow, a few cells are updated every fluid tick.
+/// Water falls as much as it can and spreads to the sides one unit at a time,
+/// it's never created or destroyed so the total volume is conserved.
+#[derive(Resource, Default)]
+pub struct FluidSim {
+    queue: VecDeque<BlockPos>,
+    queued: HashSet<BlockPos>,
+}
+
+impl FluidSim {
+    pub fn wake(&mut self, pos: BlockPos) {
+        if self.queued.insert(pos) {
+            self.queue.push_back(pos);
+        }
+    }
+
+    /// Wakes pos and its 6 neighbours
+    pub fn wake_around(&mut self, pos: BlockPos) {
+        self.wake(pos);
+        for offset in SIDES.into_iter().chain([(0, -1, 0), (0, 1, 0)]) {
+            self.wake(pos + offset);
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Updates up to `budget` cells, returns the number of cells updated
+    pub fn step(&mut self, world: &VoxelWorld, budget: usize) -> usize {
+        let mut updated = 0;
+        while updated < budget {
+            let Some(pos) = self.queue.pop_front() else {
+                break;
+            };
+            self.queued.remove(&pos);
+            self.update(world, pos);
+            updated += 1;
+        }
+        updated
+    }
+
+    fn update(&mut self, world: &VoxelWorld, pos: BlockPos) {
+        let Some(mut volume) = water_volume(world, pos) else {
+            return;
+        };
+        if volume == 0 {
+            return;
+        }
+        let mut changed = Vec::new();
+        let below = pos + (0, -1, 0);
+        if let Some(below_volume) = water_volume(world, below) {
+            let flow = volume.min(max_volume() - below_volume);
+            if flow > 0 {
+                set_water_volume(world, below, below_volume + flow);
+                volume -= flow;
+                changed.push(below);
+            }
+        }
+        // water only spreads towards cells that have at least 2 less, so it settles instead of flowing back and forth
+        let mut sides: Vec<_> = SIDES.into_iter()
+            .filter_map(|offset| water_volume(world, pos + offset).map(|side_volume| (pos + offset, side_volume)))
+            .collect();
+        sides.sort_by_key(|(_, side_volume)| *side_volume);
+        for (side, side_volume) in sides {
+            if side_volume + 1 < volume {
+                set_water_volume(world, side, side_volume + 1);
+                volume -= 1;
+                changed.push(side);
+            }
+        }
+        if changed.is_empty() {
+            return;
+        }
+        set_water_volume(world, pos, volume);
+        self.wake_around(pos);
+        for pos in changed {
+            self.wake_around(pos);
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct FluidTimer(pub Timer);
+
+/// Edits near water make it flow again
+pub fn wake_fluids(mut sim: ResMut<FluidSim>, mut block_changes: EventReader<BlockChanged>) {
+    for change in block_changes.read() {
+        if change.cause != BlockChangeCause::Fluid {
+            sim.wake_around(change.pos);
+        }
+    }
+}
+
+pub fn step_fluids(world: Res<VoxelWorld>, mut sim: ResMut<FluidSim>, mut timer: ResMut<FluidTimer>, time: Res<Time>) {
+    timer.0.tick(time.delta());
+    if timer.0.finished() {
+        sim.step(&world, FLUID_BUDGET);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::iproduct;
+    use crate::{world::{BlockChangeCause, BlockPos, Realm, VoxelWorld, CHUNK_S1}, Block};
+    use super::{water_volume, FluidSim};
+
+    fn total_volume(world: &VoxelWorld) -> u32 {
+        iproduct!(0..CHUNK_S1 as i32, 0..CHUNK_S1 as i32, 0..CHUNK_S1 as i32)
+            .filter_map(|(x, y, z)| water_volume(world, BlockPos { x, y, z, realm: Realm::Overworld }))
+            .map(|volume| volume as u32)
+            .sum()
+    }
+
+    #[test]
+    fn test_volume_is_conserved() {
+        let world = VoxelWorld::new();
+        let mut sim = FluidSim::default();
+        let origin = BlockPos { x: 0, y: 0, z: 0, realm: Realm::Overworld };
+        for (x, z) in iproduct!(0..30, 0..30) {
+            world.set_block(origin + (x, 10, z), Block::Granite, BlockChangeCause::Gen);
+        }
+        // a wall that water has to go around
+        for z in 5..30 {
+            world.set_block(origin + (12, 11, z), Block::Granite, BlockChangeCause::Gen);
+        }
+        for y in 11..17 {
+            world.set_block(origin + (15, y, 15), Block::SeaBlock, BlockChangeCause::Gen);
+            sim.wake(origin + (15, y, 15));
+        }
+        let volume = total_volume(&world);
+        assert_eq!(volume, 6 * 8);
+        let mut steps = 0;
+        while !sim.is_idle() && steps < 10_000 {
+            sim.step(&world, 100);
+            steps += 1;
+        }
+        assert!(sim.is_idle());
+        assert_eq!(total_volume(&world), volume);
+        // it all fell on the floor
+        assert_eq!(world.get_block(origin + (15, 12, 15)), Block::Air);
+        assert_eq!(world.get_block(origin + (15, 11, 15)), Block::SeaBlock);
+    }
+}
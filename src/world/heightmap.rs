@@ -0,0 +1,132 @@
+use itertools::iproduct;
+use crate::Block;
+use super::{BlockPos, BlockPos2d, Chunk, ChunkPos, ColPos, ColedPos, VoxelWorld, CHUNK_S1, CHUNK_S2, Y_CHUNKS};
+
+/// What a heightmap tracks in each column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Heightmap {
+    /// Highest block that entities can't go through
+    Solid,
+    /// Highest block that isn't Air
+    Surface,
+    /// Highest block that stops light
+    Opaque,
+}
+
+impl Heightmap {
+    const ALL: [Heightmap; 3] = [Heightmap::Solid, Heightmap::Surface, Heightmap::Opaque];
+
+    pub fn matches(&self, block: Block) -> bool {
+        match self {
+            Heightmap::Solid => !block.is_traversable(),
+            Heightmap::Surface => block != Block::Air,
+            Heightmap::Opaque => block.is_opaque(),
+        }
+    }
+}
+
+/// Heightmaps of a column, a height is the y right above the highest matching block so 0 means there's none
+#[derive(Debug, Clone)]
+pub struct ColHeights(Vec<[u16; 3]>);
+
+impl Default for ColHeights {
+    fn default() -> Self {
+        ColHeights(vec![[0; 3]; CHUNK_S2])
+    }
+}
+
+fn height_index((x, z): ColedPos) -> usize {
+    x * CHUNK_S1 + z
+}
+
+impl VoxelWorld {
+    /// Height of the heightmap at pos, None if the column isn't loaded
+    pub fn height(&self, pos: BlockPos2d, heightmap: Heightmap) -> Option<i32> {
+        let (col_pos, coled_pos) = pos.into();
+        let heights = self.heightmaps.get(&col_pos)?;
+        Some(heights.0[height_index(coled_pos)][heightmap as usize] as i32)
+    }
+
+    /// Computes the heightmaps of a column that was just generated or loaded, it's considered loaded from now on
+    pub fn compute_heightmaps(&self, col_pos: ColPos) {
+        let mut heights = ColHeights::default();
+        for cy in (0..Y_CHUNKS as i32).rev() {
+            let chunk_pos = ChunkPos { x: col_pos.x, y: cy, z: col_pos.z, realm: col_pos.realm };
+            let Some(chunk) = self.chunks.get(&chunk_pos) else {
+                continue;
+            };
+            if matches!(**chunk, Chunk::Uniform(Block::Air)) {
+                continue;
+            }
+            for ((x, z), (i, heightmap)) in iproduct!(iproduct!(0..CHUNK_S1, 0..CHUNK_S1), Heightmap::ALL.into_iter().enumerate()) {
+                let height = &mut heights.0[height_index((x, z))][i];
+                if *height > 0 {
+                    continue;
+                }
+                if let Some(y) = (0..CHUNK_S1).rev().find(|y| heightmap.matches(*chunk.get((x, *y, z)))) {
+                    *height = (cy as usize * CHUNK_S1 + y + 1) as u16;
+                }
+            }
+        }
+        self.heightmaps.insert(col_pos, heights);
+    }
+
+    /// Keeps the heightmaps up to date after block was placed at pos
+    pub(super) fn update_heightmaps(&self, pos: BlockPos, block: Block) {
+        let (col_pos, coled_pos) = BlockPos2d::from(pos).into();
+        let i = height_index(coled_pos);
+        let Some(old_heights) = self.heightmaps.get(&col_pos).map(|heights| heights.0[i]) else {
+            return;
+        };
+        let mut heights = old_heights;
+        for (height, heightmap) in heights.iter_mut().zip(Heightmap::ALL) {
+            if heightmap.matches(block) {
+                *height = (*height).max(pos.y as u16 + 1);
+            } else if *height as i32 == pos.y + 1 {
+                // the highest block was removed, look for the next one below
+                *height = (0..pos.y).rev()
+                    .find(|y| heightmap.matches(self.get_block(BlockPos { y: *y, ..pos })))
+                    .map_or(0, |y| y as u16 + 1);
+            }
+        }
+        if heights != old_heights {
+            if let Some(mut col_heights) = self.heightmaps.get_mut(&col_pos) {
+                col_heights.0[i] = heights;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Vec3;
+    use crate::{world::{BlockChangeCause, BlockPos, BlockPos2d, ColPos, Realm, VoxelWorld}, Block};
+    use super::Heightmap;
+
+    #[test]
+    fn test_heightmaps_follow_edits() {
+        let world = VoxelWorld::new();
+        let col = ColPos { x: 0, z: 0, realm: Realm::Overworld };
+        let pos2d = BlockPos2d { x: 3, z: 4, realm: Realm::Overworld };
+        let pos = BlockPos { x: 3, y: 0, z: 4, realm: Realm::Overworld };
+        assert_eq!(world.height(pos2d, Heightmap::Surface), None);
+        world.set_yrange(col, (3, 4), 70, 10, Block::Granite);
+        world.set_block(pos + (0, 71, 0), Block::SeaBlock, BlockChangeCause::Gen);
+        world.compute_heightmaps(col);
+        assert_eq!(world.height(pos2d, Heightmap::Solid), Some(71));
+        assert_eq!(world.height(pos2d, Heightmap::Surface), Some(72));
+        assert_eq!(world.top_block(pos2d), (Block::SeaBlock, 71));
+        // glass is solid but lets the light through
+        world.set_block(pos + (0, 100, 0), Block::Glass, BlockChangeCause::Player);
+        assert_eq!(world.height(pos2d, Heightmap::Solid), Some(101));
+        assert_eq!(world.height(pos2d, Heightmap::Opaque), Some(71));
+        world.set_block(pos + (0, 100, 0), Block::Air, BlockChangeCause::Player);
+        world.set_block(pos + (0, 70, 0), Block::Air, BlockChangeCause::Player);
+        assert_eq!(world.height(pos2d, Heightmap::Solid), Some(70));
+        assert_eq!(world.height(pos2d, Heightmap::Surface), Some(72));
+        assert_eq!(world.height(BlockPos2d { x: 0, z: 0, realm: Realm::Overworld }, Heightmap::Surface), Some(0));
+        // x = -0.5 is in column -1, which isn't loaded
+        assert!(world.is_col_loaded(Vec3::new(0.5, 80., 0.5), Realm::Overworld));
+        assert!(!world.is_col_loaded(Vec3::new(-0.5, 80., 0.5), Realm::Overworld));
+    }
+}
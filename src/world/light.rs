@@ -0,0 +1,362 @@
+use std::collections::{HashSet, VecDeque};
+use bevy::prelude::{EventReader, Res};
+use itertools::iproduct;
+use strum::IntoEnumIterator;
+use crate::{block::Face, Block};
+use super::{
+    pos2d::chunks_in_col, BlockChanged, BlockPos, BlockPos2d, Chunk, ChunkPos, ChunkedPos, ColPos, Heightmap, VoxelWorld,
+    CHUNK_S1, CHUNK_S2, MAX_HEIGHT, Y_CHUNKS,
+};
+
+/// Light of a voxel, sky light in the 4 high bits and block light in the 4 low bits
+pub type Light = u8;
+pub const MAX_LIGHT: u8 = 15;
+/// Full sky light and no block light
+pub const SKY_LIGHT: Light = MAX_LIGHT << 4;
+
+/// The brightest of the sky and block light
+pub fn light_level(light: Light) -> u8 {
+    (light >> 4).max(light & MAX_LIGHT)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LightChannel {
+    Sky,
+    Block,
+}
+
+impl LightChannel {
+    fn get(&self, light: Light) -> u8 {
+        match self {
+            LightChannel::Sky => light >> 4,
+            LightChannel::Block => light & MAX_LIGHT,
+        }
+    }
+
+    fn with(&self, light: Light, level: u8) -> Light {
+        match self {
+            LightChannel::Sky => (light & MAX_LIGHT) | (level << 4),
+            LightChannel::Block => (light & (MAX_LIGHT << 4)) | level,
+        }
+    }
+}
+
+/// Light of every voxel of a chunk.
+/// Chunks that were never lit are considered to be above the terrain, so fully sky lit.
+#[derive(Debug)]
+pub enum ChunkLight {
+    Uniform(Light),
+    Full(Vec<Light>),
+}
+
+impl Default for ChunkLight {
+    fn default() -> Self {
+        ChunkLight::Uniform(SKY_LIGHT)
+    }
+}
+
+fn light_index((x, y, z): ChunkedPos) -> usize {
+    z + x * CHUNK_S1 + y * CHUNK_S2
+}
+
+impl ChunkLight {
+    pub fn get(&self, pos: ChunkedPos) -> Light {
+        match self {
+            ChunkLight::Uniform(light) => *light,
+            ChunkLight::Full(lights) => lights[light_index(pos)],
+        }
+    }
+
+    pub fn set(&mut self, pos: ChunkedPos, light: Light) {
+        if let ChunkLight::Uniform(uniform) = self {
+            if *uniform == light {
+                return;
+            }
+            *self = ChunkLight::Full(vec![*uniform; CHUNK_S1.pow(3)]);
+        }
+        if let ChunkLight::Full(lights) = self {
+            lights[light_index(pos)] = light;
+        }
+    }
+}
+
+impl From<Vec<Light>> for ChunkLight {
+    fn from(lights: Vec<Light>) -> Self {
+        if lights.iter().all(|light| *light == lights[0]) {
+            ChunkLight::Uniform(lights[0])
+        } else {
+            ChunkLight::Full(lights)
+        }
+    }
+}
+
+fn neighbours(pos: BlockPos) -> impl Iterator<Item = (Face, BlockPos)> {
+    Face::iter()
+        .map(move |face| {
+            let [dx, dy, dz] = face.n();
+            (face, pos + (dx, dy, dz))
+        })
+        .filter(|(_, neighbour)| neighbour.y >= 0 && neighbour.y < MAX_HEIGHT as i32)
+}
+
+impl VoxelWorld {
+    pub fn get_light(&self, pos: BlockPos) -> Light {
+        if pos.y >= MAX_HEIGHT as i32 {
+            return SKY_LIGHT;
+        }
+        if pos.y < 0 {
+            return 0;
+        }
+        let (chunk_pos, chunked_pos) = <(ChunkPos, ChunkedPos)>::from(pos);
+        match self.chunks.get(&chunk_pos) {
+            None => SKY_LIGHT,
+            Some(chunk) => chunk.light.get(chunked_pos),
+        }
+    }
+
+    // Returns false if the chunk doesn't exist, touched collects the chunks that need to be remeshed
+    fn set_light(&self, pos: BlockPos, light: Light, touched: &mut HashSet<ChunkPos>) -> bool {
+        let (chunk_pos, chunked_pos) = <(ChunkPos, ChunkedPos)>::from(pos);
+        let Some(mut chunk) = self.chunks.get_mut(&chunk_pos) else {
+            return false;
+        };
+        chunk.light.set(chunked_pos, light);
+        touched.insert(chunk_pos);
+        touched.extend(VoxelWorld::border_neighbours(chunk_pos, chunked_pos));
+        true
+    }
+
+    fn mark_light_change(&self, touched: HashSet<ChunkPos>) {
+        for chunk_pos in touched {
+            if chunk_pos.y >= 0 && chunk_pos.y < Y_CHUNKS as i32 {
+                self.mark_change_single(chunk_pos);
+            }
+        }
+    }
+
+    // Flood fill from every position of the queue.
+    // Light goes through non opaque blocks, losing 1 level per block, except full sky light that goes down through air.
+    fn propagate(&self, channel: LightChannel, queue: &mut VecDeque<BlockPos>, touched: &mut HashSet<ChunkPos>) {
+        while let Some(pos) = queue.pop_front() {
+            let level = channel.get(self.get_light(pos));
+            if level <= 1 {
+                continue;
+            }
+            for (face, neighbour) in neighbours(pos) {
+                let block = self.get_block(neighbour);
+                if block.is_opaque() {
+                    continue;
+                }
+                let new_level = if channel == LightChannel::Sky && face == Face::Down && level == MAX_LIGHT && block == Block::Air {
+                    MAX_LIGHT
+                } else {
+                    level - 1
+                };
+                let light = self.get_light(neighbour);
+                if channel.get(light) < new_level && self.set_light(neighbour, channel.with(light, new_level), touched) {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    // Removes the light that came through pos, then lights it again from its surroundings
+    fn relight_channel(&self, channel: LightChannel, pos: BlockPos, touched: &mut HashSet<ChunkPos>) {
+        let mut removals = VecDeque::new();
+        let mut additions = VecDeque::new();
+        let light = self.get_light(pos);
+        let level = channel.get(light);
+        if level > 0 && self.set_light(pos, channel.with(light, 0), touched) {
+            removals.push_back((pos, level));
+        }
+        while let Some((pos, level)) = removals.pop_front() {
+            for (face, neighbour) in neighbours(pos) {
+                let light = self.get_light(neighbour);
+                let n_level = channel.get(light);
+                if n_level == 0 {
+                    continue;
+                }
+                let from_above = channel == LightChannel::Sky && face == Face::Down && level == MAX_LIGHT && n_level == MAX_LIGHT;
+                let is_source = channel == LightChannel::Block && self.get_block(neighbour).light_emission() > 0;
+                if (n_level < level || from_above) && !is_source {
+                    if self.set_light(neighbour, channel.with(light, 0), touched) {
+                        removals.push_back((neighbour, n_level));
+                    }
+                } else {
+                    // lit by something else, it will light the removed area back
+                    additions.push_back(neighbour);
+                }
+            }
+        }
+        let block = self.get_block(pos);
+        if channel == LightChannel::Block && block.light_emission() > 0 {
+            let light = self.get_light(pos);
+            self.set_light(pos, channel.with(light, block.light_emission()), touched);
+            additions.push_back(pos);
+        }
+        if !block.is_opaque() {
+            additions.extend(neighbours(pos).map(|(_, neighbour)| neighbour));
+        }
+        self.propagate(channel, &mut additions, touched);
+    }
+
+    /// Updates the light around a block that changed and marks the affected chunks as changed
+    pub fn relight(&self, pos: BlockPos) {
+        let mut touched = HashSet::new();
+        self.relight_channel(LightChannel::Sky, pos, &mut touched);
+        self.relight_channel(LightChannel::Block, pos, &mut touched);
+        self.mark_light_change(touched);
+    }
+
+    // Lowest y of the column at pos that sees the sky, None if the column isn't loaded
+    fn sky_floor(&self, pos: BlockPos2d) -> Option<i32> {
+        self.height(pos, Heightmap::Surface)
+    }
+
+    /// Computes the light of a column that was just generated or loaded,
+    /// and lets the light of the neighbouring columns flow in and out of it.
+    pub fn light_col(&self, col_pos: ColPos) {
+        let mut touched = HashSet::new();
+        let mut sky_queue = VecDeque::new();
+        let mut block_queue = VecDeque::new();
+        // lowest y that sees the sky, for each (x, z) of the column
+        let mut floors = vec![0; CHUNK_S2];
+        let mut open = vec![true; CHUNK_S2];
+        for cy in (0..Y_CHUNKS as i32).rev() {
+            let chunk_pos = ChunkPos { x: col_pos.x, y: cy, z: col_pos.z, realm: col_pos.realm };
+            let Some(mut chunk) = self.chunks.get_mut(&chunk_pos) else {
+                continue;
+            };
+            let uniform = match **chunk {
+                Chunk::Uniform(block) => Some(block),
+                _ => None,
+            };
+            match uniform {
+                Some(Block::Air) if open.iter().all(|open| *open) => {
+                    chunk.light = ChunkLight::Uniform(SKY_LIGHT);
+                    continue;
+                },
+                Some(block) if block != Block::Air && block.light_emission() == 0 => {
+                    for (floor, open) in floors.iter_mut().zip(open.iter_mut()) {
+                        if *open {
+                            *open = false;
+                            *floor = (cy + 1) * CHUNK_S1 as i32;
+                        }
+                    }
+                    chunk.light = ChunkLight::Uniform(0);
+                    continue;
+                },
+                _ => {}
+            }
+            drop(chunk);
+            self.light_chunk(chunk_pos, &mut open, &mut floors, &mut block_queue);
+        }
+        // sky lit voxels next to darker ones start the flood fill
+        for (x, z) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1) {
+            let floor = floors[x * CHUNK_S1 + z];
+            let highest_neighbour_floor = [(-1, 0), (1, 0), (0, -1), (0, 1)].into_iter().filter_map(|(dx, dz)| {
+                let (nx, nz) = (x as i32 + dx, z as i32 + dz);
+                if nx < 0 || nz < 0 || nx >= CHUNK_S1 as i32 || nz >= CHUNK_S1 as i32 {
+                    let pos = BlockPos2d::from((col_pos, (x, z)));
+                    self.sky_floor(BlockPos2d { x: pos.x + dx, z: pos.z + dz, realm: pos.realm })
+                } else {
+                    Some(floors[nx as usize * CHUNK_S1 + nz as usize])
+                }
+            }).max().unwrap_or(0);
+            for y in floor..highest_neighbour_floor {
+                sky_queue.push_back(BlockPos::from((col_pos, (x, y, z))));
+            }
+        }
+        // light from the neighbouring columns flows in
+        for (dx, dz) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let neighbour_col = ColPos { x: col_pos.x + dx, z: col_pos.z + dz, realm: col_pos.realm };
+            if !chunks_in_col(&neighbour_col).iter().any(|chunk_pos| self.chunks.contains_key(chunk_pos)) {
+                continue;
+            }
+            for (i, y) in iproduct!(0..CHUNK_S1, 0..MAX_HEIGHT as i32) {
+                let (x, z) = match (dx, dz) {
+                    (-1, _) => (0, i),
+                    (1, _) => (CHUNK_S1 - 1, i),
+                    (_, -1) => (i, 0),
+                    _ => (i, CHUNK_S1 - 1),
+                };
+                let pos = BlockPos::from((col_pos, (x, y, z))) + (dx, 0, dz);
+                let light = self.get_light(pos);
+                if LightChannel::Sky.get(light) > 1 && y < floors[x * CHUNK_S1 + z] {
+                    sky_queue.push_back(pos);
+                }
+                if LightChannel::Block.get(light) > 1 {
+                    block_queue.push_back(pos);
+                }
+            }
+        }
+        self.propagate(LightChannel::Sky, &mut sky_queue, &mut touched);
+        self.propagate(LightChannel::Block, &mut block_queue, &mut touched);
+        self.mark_light_change(touched);
+    }
+
+    // Sky light goes straight down until the first non air block, emitters are added to the queue
+    fn light_chunk(&self, chunk_pos: ChunkPos, open: &mut [bool], floors: &mut [i32], block_queue: &mut VecDeque<BlockPos>) {
+        let Some(mut chunk) = self.chunks.get_mut(&chunk_pos) else {
+            return;
+        };
+        let mut lights = vec![0; CHUNK_S1.pow(3)];
+        for y in (0..CHUNK_S1).rev() {
+            for (x, z) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1) {
+                let block = *chunk.get((x, y, z));
+                let i = x * CHUNK_S1 + z;
+                let mut light = 0;
+                if open[i] && block == Block::Air {
+                    light = SKY_LIGHT;
+                } else if open[i] {
+                    open[i] = false;
+                    floors[i] = chunk_pos.y * CHUNK_S1 as i32 + y as i32 + 1;
+                }
+                if block.light_emission() > 0 {
+                    light = LightChannel::Block.with(light, block.light_emission());
+                    block_queue.push_back(BlockPos::from((chunk_pos, (x, y, z))));
+                }
+                lights[light_index((x, y, z))] = light;
+            }
+        }
+        chunk.light = ChunkLight::from(lights);
+    }
+}
+
+/// Keeps the light up to date when blocks change
+pub fn update_light(world: Res<VoxelWorld>, mut block_changes: EventReader<BlockChanged>) {
+    for change in block_changes.read() {
+        world.relight(change.pos);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::iproduct;
+    use crate::{world::{BlockChangeCause, BlockPos, ColPos, EditOp, EditShape, Realm, VoxelWorld, CHUNK_S1}, Block};
+    use super::{light_level, MAX_LIGHT};
+
+    #[test]
+    fn test_cave_light() {
+        let world = VoxelWorld::new();
+        let col = ColPos { x: 0, z: 0, realm: Realm::Overworld };
+        let origin = BlockPos { x: 0, y: 0, z: 0, realm: Realm::Overworld };
+        for (x, z) in iproduct!(0..CHUNK_S1, 0..CHUNK_S1) {
+            world.set_yrange(col, (x, z), 99, 99, Block::Granite);
+        }
+        // a closed room underground
+        let room = origin + (10, 50, 10);
+        world.edit(EditShape::Cuboid(room, room + (4, 2, 4)), EditOp::Fill(Block::Air), BlockChangeCause::Gen);
+        world.light_col(col);
+        assert_eq!(light_level(world.get_light(origin + (5, 100, 5))), MAX_LIGHT);
+        assert_eq!(world.get_light(room), 0);
+        // a lit campfire lights the room, less and less with distance
+        world.set_block(room, Block::CampfireOn, BlockChangeCause::Gen);
+        world.relight(room);
+        assert_eq!(light_level(world.get_light(room + (1, 0, 0))), 14);
+        assert_eq!(light_level(world.get_light(room + (4, 0, 0))), 11);
+        world.set_block(room, Block::Air, BlockChangeCause::Gen);
+        world.relight(room);
+        assert_eq!(world.get_light(room + (4, 0, 0)), 0);
+    }
+}
@@ -7,7 +7,8 @@ use std::{collections::HashMap, ops::RangeInclusive};
 #[derive(Component, Clone, Copy)]
 pub struct RenderDistance(pub u32);
 
-#[derive(Resource, Clone)]
+/// The columns kept loaded around an entity, with their distance to it
+#[derive(Component, Clone)]
 pub struct PlayerArea {
     pub center: ColPos,
     pub col_dists: HashMap<ColPos, u32>,
@@ -48,7 +49,8 @@ impl PlayerArea {
         chunks
             .iter()
             .filter_map(|entry| {
-                if entry.value().changed {
+                // chunks outside of the area are left for the areas that contain them
+                if entry.value().changed && self.col_dists.contains_key(&(*entry.key()).into()) {
                     Some(*entry.key())
                 } else {
                     None
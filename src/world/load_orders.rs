@@ -1,10 +1,11 @@
 use super::BlockPos;
 use super::{
-    pos2d::Pos2d, utils::ReinsertTrait, ColPos, PlayerArea, Realm, RenderDistance, VoxelWorld,
-    CHUNK_S1,
+    pos2d::Pos2d, ColPos, PlayerArea, Realm, Regions, RenderDistance,
+    VoxelWorld, CHUNK_S1,
 };
+use bevy::app::AppExit;
 use bevy::prelude::*;
-use itertools::Itertools;
+use crossbeam::queue::SegQueue;
 use parking_lot::lock_api::ArcRwLockWriteGuard;
 use parking_lot::{RawRwLock, RwLock};
 use std::collections::{HashMap, HashSet};
@@ -36,20 +37,19 @@ fn update_gen_order(
     else {
         return;
     };
-    let new_i = match to_generate.binary_search_by(|(_, other_dist)| dist.cmp(other_dist)) {
-        Ok(i) => i,
-        Err(i) => i,
-    };
-    to_generate.reinsert(old_i, new_i);
+    to_generate.remove(old_i);
+    add_gen_order(to_generate, *col_pos, dist);
 }
 
 #[derive(Resource)]
 pub struct LoadOrders {
-    // { column: { player } }
-    player_cols: HashMap<ColPos, HashSet<u32>>,
-    // [(column, min dist to player)]
+    // { column: { player: dist to player } }
+    player_cols: HashMap<ColPos, HashMap<u32, u32>>,
+    // [(column, min dist to players)]
     pub to_generate: Arc<RwLock<Vec<(ColPos, u32)>>>,
     pub to_unload: Vec<ColPos>,
+    // columns the gen thread finished loading, sent as ColLoadEvent every frame
+    pub loaded: Arc<SegQueue<ColPos>>,
 }
 
 impl LoadOrders {
@@ -58,22 +58,29 @@ impl LoadOrders {
             player_cols: HashMap::new(),
             to_generate: Arc::new(RwLock::new(Vec::new())),
             to_unload: Vec::new(),
+            loaded: Arc::new(SegQueue::new()),
         }
     }
 
-    fn unload_col(&mut self, col_pos: ColPos) {
+    // player_id doesn't need the column anymore, it's unloaded if no other player needs it
+    fn release_col(
+        &mut self,
+        to_generate: &mut ArcRwLockWriteGuard<RawRwLock, Vec<(Pos2d<CHUNK_S1>, u32)>>,
+        player_id: u32,
+        col_pos: ColPos,
+    ) {
+        let Some(players) = self.player_cols.get_mut(&col_pos) else {
+            return;
+        };
+        players.remove(&player_id);
+        if let Some(dist) = players.values().min() {
+            update_gen_order(to_generate, &col_pos, *dist);
+            return;
+        }
         self.player_cols.remove(&col_pos);
-        // NOTE: very important to store this in an intermediary variable
-        // or else the read lock lives long enough that we reach the write lock in the if
-        let generate_order_opt = self
-            .to_generate
-            .read_arc()
-            .iter()
-            .find_position(|(pos_, _)| *pos_ == col_pos)
-            .map(|(i, _)| i);
-        if let Some(i) = generate_order_opt {
+        if let Some(i) = to_generate.iter().position(|(pos_, _)| *pos_ == col_pos) {
             // the column was still waiting for load
-            self.to_generate.write_arc().remove(i);
+            to_generate.remove(i);
         } else {
             self.to_unload.push(col_pos);
         }
@@ -85,54 +92,63 @@ impl LoadOrders {
         old_load_area: &PlayerArea,
         new_load_area: &PlayerArea,
     ) {
+        // NOTE: the write lock is taken for the whole update or else the gen thread could pop orders in between
+        let mut wlock: ArcRwLockWriteGuard<RawRwLock, Vec<(Pos2d<CHUNK_S1>, u32)>> =
+            self.to_generate.write_arc();
         for col_pos in old_load_area.col_dists.keys() {
-            if new_load_area.col_dists.contains_key(col_pos) {
-                continue;
-            }
-            if let Some(players) = self.player_cols.get_mut(col_pos) {
-                players.remove(&player_id);
-                if players.is_empty() {
-                    self.unload_col(*col_pos);
-                }
+            if !new_load_area.col_dists.contains_key(col_pos) {
+                self.release_col(&mut wlock, player_id, *col_pos);
             }
         }
-        let mut wlock: ArcRwLockWriteGuard<RawRwLock, Vec<(Pos2d<CHUNK_S1>, u32)>> =
-            self.to_generate.write_arc();
         for (col_pos, dist) in new_load_area.col_dists.iter() {
-            if old_load_area.col_dists.contains_key(col_pos) {
-                continue;
-            }
             let players = self.player_cols.entry(*col_pos).or_default();
-            let is_new = players.is_empty();
-            players.insert(player_id);
-            if is_new {
-                add_gen_order(&mut wlock, *col_pos, *dist);
-            } else {
-                update_gen_order(&mut wlock, col_pos, *dist)
+            let old_min = players.values().min().copied();
+            players.insert(player_id, *dist);
+            // generation priority is the distance to the closest player
+            let new_min = *players.values().min().unwrap();
+            match old_min {
+                None => add_gen_order(&mut wlock, *col_pos, new_min),
+                Some(old_min) if old_min != new_min => update_gen_order(&mut wlock, col_pos, new_min),
+                _ => {}
             }
         }
     }
+
+    /// Releases every column needed by player_id, for players that are gone
+    pub fn remove_player(&mut self, player_id: u32) {
+        let mut wlock = self.to_generate.write_arc();
+        let cols: Vec<ColPos> = self.player_cols
+            .iter()
+            .filter(|(_, players)| players.contains_key(&player_id))
+            .map(|(col_pos, _)| *col_pos)
+            .collect();
+        for col_pos in cols {
+            self.release_col(&mut wlock, player_id, col_pos);
+        }
+    }
 }
 
+/// Gives a load area to every entity with a render distance that doesn't have one yet:
+/// players, but also anything that should keep the world loaded around it
 pub fn assign_load_area(
     mut commands: Commands,
-    mut query: Query<(Entity, &Transform, &Realm, &RenderDistance)>,
+    query: Query<(Entity, &Transform, &Realm, &RenderDistance), Without<PlayerArea>>,
     mut col_orders: ResMut<LoadOrders>,
 ) {
-    let (player, transform, realm, render_dist) = query.single_mut();
-    let col = ColPos::from((transform.translation, *realm));
-    let old_load_area = PlayerArea::empty();
-    let new_load_area = PlayerArea::new(col, *render_dist);
-    col_orders.on_load_area_change(player.index(), &old_load_area, &new_load_area);
-    commands.insert_resource(new_load_area.clone());
+    for (player, transform, realm, render_dist) in query.iter() {
+        let col = ColPos::from((transform.translation, *realm));
+        let old_load_area = PlayerArea::empty();
+        let new_load_area = PlayerArea::new(col, *render_dist);
+        col_orders.on_load_area_change(player.index(), &old_load_area, &new_load_area);
+        commands.entity(player).insert(new_load_area);
+    }
 }
 
 pub fn update_load_area(
-    mut query: Query<(Entity, &Transform, &Realm, &RenderDistance)>,
+    mut query: Query<(Entity, &Transform, &Realm, &RenderDistance, &mut PlayerArea)>,
     mut col_orders: ResMut<LoadOrders>,
-    mut load_area: ResMut<PlayerArea>,
 ) {
-    for (player, transform, realm, render_dist) in query.iter_mut() {
+    for (player, transform, realm, render_dist, mut load_area) in query.iter_mut() {
         let col = ColPos::from((transform.translation, *realm));
         // we're checking before modifying to avoid triggering unnecessary Change detection
         if col != load_area.center {
@@ -144,28 +160,30 @@ pub fn update_load_area(
 }
 
 pub fn on_render_distance_change(
-    mut query: Query<(Entity, &RenderDistance), Changed<RenderDistance>>,
+    mut query: Query<(Entity, &RenderDistance, &mut PlayerArea), Changed<RenderDistance>>,
     mut col_orders: ResMut<LoadOrders>,
-    mut load_area: ResMut<PlayerArea>,
 ) {
-    for (player, render_dist) in query.iter_mut() {
+    for (player, render_dist, mut load_area) in query.iter_mut() {
         let new_load_area = PlayerArea::new(load_area.center, *render_dist);
         col_orders.on_load_area_change(player.index(), &load_area, &new_load_area);
         *load_area = new_load_area;
     }
 }
 
+/// The columns of despawned players (or ones that lost their load area) are released
+pub fn on_load_area_removed(
+    mut removed: RemovedComponents<PlayerArea>,
+    mut col_orders: ResMut<LoadOrders>,
+) {
+    for player in removed.read() {
+        col_orders.remove_player(player.index());
+    }
+}
+
 #[derive(Default, Resource)]
 pub struct BlockEntities(HashMap<ColPos, HashMap<(usize, i32, usize), Entity>>);
 
 impl BlockEntities {
-    pub fn unload_col(&mut self, col_pos: &ColPos) -> Vec<Entity> {
-        let Some(entities) = self.0.remove(col_pos) else {
-            return Vec::new()
-        };
-        entities.into_values().into_iter().collect()
-    }
-
     pub fn get(&self, block_pos: &BlockPos) -> Option<Entity> {
         let (col_pos, pos) = (*block_pos).into();
         let col_ents = self.0.get(&col_pos)?;
@@ -189,21 +207,72 @@ impl BlockEntities {
 #[derive(Event)]
 pub struct ColUnloadEvent(pub ColPos);
 
+#[derive(Event)]
+pub struct ColLoadEvent(pub ColPos);
+
+pub fn send_col_loads(col_orders: Res<LoadOrders>, mut ev_load: EventWriter<ColLoadEvent>) {
+    while let Some(col) = col_orders.loaded.pop() {
+        ev_load.send(ColLoadEvent(col));
+    }
+}
+
 pub fn process_unload_orders(
-    mut commands: Commands,
     mut col_orders: ResMut<LoadOrders>,
     blocks: ResMut<VoxelWorld>,
+    regions: Res<Regions>,
     mut ev_unload: EventWriter<ColUnloadEvent>,
-    mut col_entities: ResMut<BlockEntities>,
 ) {
     // PROCESS UNLOAD ORDERS
+    // block entities are kept so that furnaces carry on with their content when the column is loaded again
     for col in col_orders.to_unload.drain(..) {
+        regions.save_col(&blocks, col);
         blocks.unload_col(col);
-        for entity_id in col_entities.unload_col(&col) {
-            if let Some(mut entity) = commands.get_entity(entity_id) {
-                entity.despawn();
-            }
-        }
         ev_unload.send(ColUnloadEvent(col));
     }
 }
+
+pub fn save_on_exit(
+    mut ev_exit: EventReader<AppExit>,
+    blocks: Res<VoxelWorld>,
+    regions: Res<Regions>,
+) {
+    if ev_exit.read().last().is_none() {
+        return;
+    }
+    let edited_cols: HashSet<ColPos> = blocks.chunks
+        .iter()
+        .filter(|entry| entry.value().edited)
+        .map(|entry| (*entry.key()).into())
+        // chunks of columns that aren't loaded only hold what spilled from their neighbours
+        .filter(|col| blocks.heightmaps.contains_key(col))
+        .collect();
+    for col in edited_cols {
+        regions.save_col(&blocks, col);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::{ColPos, PlayerArea, Realm, RenderDistance};
+    use super::LoadOrders;
+
+    #[test]
+    fn test_shared_columns() {
+        let mut orders = LoadOrders::new();
+        let a = PlayerArea::new(ColPos { x: 0, z: 0, realm: Realm::Overworld }, RenderDistance(2));
+        let b = PlayerArea::new(ColPos { x: 3, z: 0, realm: Realm::Overworld }, RenderDistance(2));
+        orders.on_load_area_change(1, &PlayerArea::empty(), &a);
+        orders.on_load_area_change(2, &PlayerArea::empty(), &b);
+        // columns needed by both are only ordered once, with the distance to the closest player
+        let shared = ColPos { x: 2, z: 0, realm: Realm::Overworld };
+        assert_eq!(orders.to_generate.read().len(), 5*5 + 5*5 - 2*5);
+        assert!(orders.to_generate.read().contains(&(shared, 1)));
+        // pretend everything was generated
+        orders.to_generate.write().clear();
+        orders.remove_player(2);
+        assert_eq!(orders.to_unload.len(), 3*5);
+        assert!(!orders.to_unload.contains(&shared));
+        orders.remove_player(1);
+        assert_eq!(orders.to_unload.len(), 5*5 + 5*5 - 2*5);
+    }
+}
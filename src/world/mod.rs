@@ -5,17 +5,50 @@ mod realm;
 mod chunk;
 mod pos;
 mod utils;
+mod region;
+mod block_changes;
+mod edit_journal;
+mod region_edit;
+mod schematic;
+mod raycast;
+mod collision;
+mod light;
+mod fluid;
+mod portal;
+mod clock;
+mod block_ticks;
+mod random_ticks;
+mod heightmap;
+mod biome;
 
 pub use realm::*;
 pub use voxel_world::*;
 pub use chunk::*;
 pub use pos::*;
+pub use region::Regions;
+pub use block_changes::{BlockChanged, BlockChangeCause};
+pub use edit_journal::EditJournal;
+pub use region_edit::{EditShape, EditOp};
+pub use schematic::Schematic;
+pub use raycast::BlockRayCastHit;
+pub use collision::CollisionBox;
+pub use light::{light_level, ChunkLight, Light, SKY_LIGHT};
+pub use fluid::FluidSim;
+pub use portal::{InPortal, PortalTransit};
+pub use clock::{WorldClock, TICKS_PER_SEC};
+pub use block_ticks::{BlockTick, BlockTicked, ScheduledTick, ScheduledTicks};
+pub use heightmap::{ColHeights, Heightmap};
+pub use biome::{biome_index, Biome, BiomeId, Biomes, ColBiomes, BIOMES_FILE};
+pub use random_ticks::{RandomTicks, RandomTickHandler, RANDOM_TICKS_PER_CHUNK};
 pub use load_area::{PlayerArea, RenderDistance, range_around};
-pub use load_orders::{LoadOrders, ColUnloadEvent, BlockEntities};
-use bevy::{app::Startup, ecs::schedule::{apply_deferred, IntoSystemConfigs, SystemSet}, prelude::{Plugin, Update}};
-use crate::{agents::PlayerSpawn, gen::setup_gen_thread};
+pub use load_orders::{LoadOrders, ColLoadEvent, ColUnloadEvent, BlockEntities};
+use std::time::Duration;
+use bevy::{app::{First, Last, Startup}, ecs::schedule::{apply_deferred, IntoSystemConfigs, SystemSet}, prelude::{Plugin, Timer, TimerMode, Update}};
+use crate::{agents::PlayerSpawn, gen::setup_gen_thread, Block, BlockFamily};
+use self::{block_changes::send_block_changes, edit_journal::record_edits, light::update_light, fluid::{step_fluids, wake_fluids, FluidTimer}, portal::{enter_portals, exit_portals}};
+use self::{clock::advance_clock, block_ticks::{grow_crops, requeue_loaded_ticks, run_block_ticks, schedule_growth}, random_ticks::{decay_leaves, run_random_ticks, spread_grass}};
 use self::{load_orders::{
-	assign_load_area, on_render_distance_change, process_unload_orders, update_load_area
+	assign_load_area, on_load_area_removed, on_render_distance_change, process_unload_orders, save_on_exit, send_col_loads, update_load_area
 }, };
 pub const CHUNK_S1: usize = 62;
 pub const CHUNK_S2: usize = CHUNK_S1.pow(2);
@@ -39,12 +72,27 @@ impl Plugin for GenPlugin {
 		app
 			.insert_resource(LoadOrders::new())
 			.insert_resource(BlockEntities::default())
+			.insert_resource(EditJournal::default())
+			.insert_resource(FluidSim::default())
+			.insert_resource(random_ticks)
+			.insert_resource(Biomes::from_file(BIOMES_FILE))
+			.insert_resource(FluidTimer(Timer::new(Duration::from_millis(100), TimerMode::Repeating)))
 			.add_event::<ColUnloadEvent>()
+			.add_event::<ColLoadEvent>()
+			.add_event::<BlockChanged>()
+			.add_event::<BlockTicked>()
 			.add_systems(Startup, setup_gen_thread)
+			.add_systems(First, (send_block_changes, send_col_loads, advance_clock))
 			.add_systems(Startup, (assign_load_area, apply_deferred).chain().in_set(LoadAreaAssigned).after(PlayerSpawn))
-			.add_systems(Update, update_load_area)
-			.add_systems(Update, on_render_distance_change)
+			.add_systems(Update, (assign_load_area, update_load_area, on_render_distance_change, on_load_area_removed))
 			.add_systems(Update, process_unload_orders)
+			.add_systems(Update, record_edits)
+			.add_systems(Update, update_light)
+			.add_systems(Update, (wake_fluids, step_fluids).chain())
+			.add_systems(Update, (enter_portals, exit_portals))
+			.add_systems(Update, ((requeue_loaded_ticks, run_block_ticks).chain(), schedule_growth, grow_crops))
+			.add_systems(Update, run_random_ticks)
+			.add_systems(Last, save_on_exit)
 		;
 	}
 }
\ No newline at end of file
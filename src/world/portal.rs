@@ -0,0 +1,241 @@
+use bevy::prelude::*;
+use itertools::iproduct;
+use crate::Block;
+use super::{BlockChangeCause, BlockPos, ColPos, PlayerArea, Realm, VoxelWorld, CHUNK_S1I, MAX_GEN_HEIGHT, MAX_HEIGHT};
+
+/// Size of the portals built on arrival, it's also the smallest portal that works
+const PORTAL_W: i32 = 2;
+const PORTAL_H: i32 = 3;
+const MAX_PORTAL_SIZE: i32 = 21;
+// how far from the arrival point an existing portal is reused instead of building a new one
+const PORTAL_SEARCH_R: i32 = 8;
+const AXES: [(i32, i32, i32); 2] = [(1, 0, 0), (0, 0, 1)];
+
+impl Realm {
+    /// Horizontal distances are divided by this factor in this realm compared to the Overworld
+    pub fn scale(&self) -> f32 {
+        match self {
+            Realm::Overworld | Realm::Aether => 1.,
+            Realm::Nether => 8.,
+        }
+    }
+
+    /// The realm a portal framed with `frame` leads to from this realm
+    pub fn portal_destination(&self, frame: Block) -> Option<Realm> {
+        match (self, frame) {
+            (Realm::Overworld, Block::Basalt) => Some(Realm::Nether),
+            (Realm::Overworld, Block::Glowstone) => Some(Realm::Aether),
+            (Realm::Nether, Block::Basalt) | (Realm::Aether, Block::Glowstone) => Some(Realm::Overworld),
+            _ => None,
+        }
+    }
+}
+
+fn offset((dx, dy, dz): (i32, i32, i32), n: i32) -> (i32, i32, i32) {
+    (dx*n, dy*n, dz*n)
+}
+
+impl VoxelWorld {
+    /// Returns the frame block if pos is inside a portal:
+    /// a vertical rectangle of Air at least PORTAL_W x PORTAL_H surrounded by a frame of a single block (corners excluded)
+    pub fn find_portal(&self, pos: BlockPos) -> Option<Block> {
+        if self.get_block(pos) != Block::Air {
+            return None;
+        }
+        let mut bottom = pos;
+        while self.get_block(bottom + (0, -1, 0)) == Block::Air {
+            bottom = bottom + (0, -1, 0);
+            if pos.y - bottom.y >= MAX_PORTAL_SIZE {
+                return None;
+            }
+        }
+        let frame = self.get_block(bottom + (0, -1, 0));
+        if !matches!(frame, Block::Basalt | Block::Glowstone) {
+            return None;
+        }
+        AXES.into_iter().any(|axis| self.is_portal(bottom, axis, frame)).then_some(frame)
+    }
+
+    fn is_portal(&self, bottom: BlockPos, axis: (i32, i32, i32), frame: Block) -> bool {
+        let mut left = bottom;
+        while self.get_block(left + offset(axis, -1)) == Block::Air {
+            left = left + offset(axis, -1);
+            if (bottom.x - left.x).abs() + (bottom.z - left.z).abs() >= MAX_PORTAL_SIZE {
+                return false;
+            }
+        }
+        let (Some(width), Some(height)) = (
+            (1..=MAX_PORTAL_SIZE).find(|w| self.get_block(left + offset(axis, *w)) != Block::Air),
+            (1..=MAX_PORTAL_SIZE).find(|h| self.get_block(left + (0, *h, 0)) != Block::Air),
+        ) else {
+            return false;
+        };
+        if width < PORTAL_W || height < PORTAL_H {
+            return false;
+        }
+        (0..width).all(|i| {
+            self.get_block(left + offset(axis, i) + (0, -1, 0)) == frame
+            && self.get_block(left + offset(axis, i) + (0, height, 0)) == frame
+            && (0..height).all(|h| self.get_block(left + offset(axis, i) + (0, h, 0)) == Block::Air)
+        }) && (0..height).all(|h| {
+            self.get_block(left + offset(axis, -1) + (0, h, 0)) == frame
+            && self.get_block(left + offset(axis, width) + (0, h, 0)) == frame
+        })
+    }
+
+    /// Builds a portal along X with the bottom left of its inside at base, clearing some room in front and behind it
+    pub fn build_portal(&self, base: BlockPos, frame: Block) {
+        for (x, y, z) in iproduct!(-1..=PORTAL_W, -1..=PORTAL_H, -1..=1) {
+            let inside_x = (0..PORTAL_W).contains(&x);
+            let block = if y == -1 || (z == 0 && (!inside_x || y == PORTAL_H)) {
+                frame
+            } else if inside_x && y < PORTAL_H {
+                Block::Air
+            } else {
+                continue;
+            };
+            self.set_block(base + (x, y, z), block, BlockChangeCause::Portal);
+        }
+    }
+
+    /// The bottom left of the inside of a portal framed with `frame` close to pos, if there's one in its column
+    fn portal_near(&self, pos: BlockPos, frame: Block) -> Option<BlockPos> {
+        let col = ColPos::from(pos);
+        iproduct!(-PORTAL_SEARCH_R..=PORTAL_SEARCH_R, -PORTAL_SEARCH_R..=PORTAL_SEARCH_R, 1..MAX_HEIGHT as i32)
+            .map(|(dx, dz, y)| BlockPos { x: pos.x + dx, y, z: pos.z + dz, realm: pos.realm })
+            .filter(|spot| ColPos::from(*spot) == col)
+            .find(|spot| self.get_block(*spot + (0, -1, 0)) == frame && self.find_portal(*spot) == Some(frame))
+    }
+
+    /// Where a portal can stand close to pos: the closest spot on the ground with room for it.
+    /// The portal is kept inside the column of pos because the neighbouring ones might not be loaded yet.
+    fn portal_spot(&self, pos: BlockPos) -> BlockPos {
+        let col = ColPos::from(pos);
+        let base = BlockPos {
+            x: pos.x.clamp(col.x*CHUNK_S1I + 1, (col.x + 1)*CHUNK_S1I - PORTAL_W - 1),
+            y: pos.y.clamp(1, MAX_GEN_HEIGHT as i32),
+            z: pos.z.clamp(col.z*CHUNK_S1I + 1, (col.z + 1)*CHUNK_S1I - 2),
+            realm: pos.realm,
+        };
+        (0..MAX_HEIGHT as i32)
+            .flat_map(|d| [base.y - d, base.y + d])
+            .filter(|y| *y >= 1 && y + PORTAL_H < MAX_HEIGHT as i32)
+            .map(|y| BlockPos { y, ..base })
+            .find(|spot| !self.get_block(*spot + (0, -1, 0)).is_traversable()
+                && (0..=PORTAL_H).all(|h| self.get_block(*spot + (0, h, 0)) == Block::Air)
+            )
+            .unwrap_or(base)
+    }
+}
+
+/// On an entity that is standing in a portal, so it has to step out of it before it can use it again
+#[derive(Component)]
+pub struct InPortal;
+
+/// On an entity that went through a portal and waits for its destination to load
+#[derive(Component)]
+pub struct PortalTransit {
+    pub frame: Block,
+    pub destination: BlockPos,
+}
+
+/// Entities that step into a portal are sent to the realm it leads to.
+/// Only entities with a load area can travel, so their destination gets loaded.
+pub fn enter_portals(
+    mut commands: Commands,
+    world: Res<VoxelWorld>,
+    mut query: Query<(Entity, &mut Transform, &mut Realm, Option<&InPortal>), (With<PlayerArea>, Without<PortalTransit>)>,
+) {
+    for (entity, mut transform, mut realm, in_portal) in query.iter_mut() {
+        let pos = BlockPos::from((transform.translation, *realm));
+        let Some(frame) = world.find_portal(pos) else {
+            if in_portal.is_some() {
+                commands.entity(entity).remove::<InPortal>();
+            }
+            continue;
+        };
+        if in_portal.is_some() {
+            continue;
+        }
+        let Some(destination) = realm.portal_destination(frame) else {
+            continue;
+        };
+        let scale = realm.scale()/destination.scale();
+        transform.translation.x *= scale;
+        transform.translation.z *= scale;
+        *realm = destination;
+        // changing realm moves the load area, so the columns of the old realm get unloaded
+        commands.entity(entity).insert(PortalTransit {
+            frame,
+            destination: BlockPos::from((transform.translation, *realm)),
+        });
+    }
+}
+
+/// When the destination column of a travelling entity is loaded,
+/// it's placed in the closest portal, which is built if there's none around.
+/// The column may already be loaded by another load area, so it's checked every frame rather than on ColLoadEvent
+pub fn exit_portals(
+    mut commands: Commands,
+    world: Res<VoxelWorld>,
+    mut query: Query<(Entity, &mut Transform, &PortalTransit)>,
+) {
+    for (entity, mut transform, transit) in query.iter_mut() {
+        if !world.heightmaps.contains_key(&ColPos::from(transit.destination)) {
+            continue;
+        }
+        let base = world.portal_near(transit.destination, transit.frame).unwrap_or_else(|| {
+            let base = world.portal_spot(transit.destination);
+            world.build_portal(base, transit.frame);
+            base
+        });
+        transform.translation = Vec3::new(base.x as f32 + 0.25, base.y as f32, base.z as f32 + 0.25);
+        commands.entity(entity).remove::<PortalTransit>().insert(InPortal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use crate::{world::{BlockChangeCause, BlockPos, ColPos, Realm, VoxelWorld}, Block};
+    use super::{exit_portals, InPortal, PortalTransit};
+
+    #[test]
+    fn test_portal() {
+        let world = VoxelWorld::new();
+        let base = BlockPos { x: 10, y: 20, z: 10, realm: Realm::Overworld };
+        world.set_block(base + (0, -5, 0), Block::Granite, BlockChangeCause::Gen);
+        world.build_portal(base, Block::Basalt);
+        assert_eq!(world.find_portal(base + (1, 2, 0)), Some(Block::Basalt));
+        assert_eq!(world.find_portal(base + (0, 0, 1)), None);
+        assert_eq!(world.portal_near(base + (3, 0, 3), Block::Basalt), Some(base));
+        assert_eq!(Realm::Overworld.portal_destination(Block::Basalt), Some(Realm::Nether));
+        // a broken frame doesn't work anymore
+        world.set_block(base + (-1, 1, 0), Block::Air, BlockChangeCause::Player);
+        assert_eq!(world.find_portal(base + (1, 2, 0)), None);
+        // portals built for a negative destination stay in its column
+        let destination = BlockPos { x: -3, y: 20, z: -3, realm: Realm::Nether };
+        let spot = world.portal_spot(destination);
+        assert_eq!(ColPos::from(spot), ColPos { x: -1, z: -1, realm: Realm::Nether });
+    }
+
+    #[test]
+    fn test_exit_to_loaded_column() {
+        let world = VoxelWorld::new();
+        // the destination column was already loaded by someone else, no ColLoadEvent is coming
+        let col = ColPos { x: -1, z: 0, realm: Realm::Nether };
+        world.set_yrange(col, (10, 10), 30, 30, Block::Granite);
+        world.compute_heightmaps(col);
+        let mut app = App::new();
+        app.insert_resource(world).add_systems(Update, exit_portals);
+        let destination = BlockPos { x: -50, y: 31, z: 10, realm: Realm::Nether };
+        let traveller = app.world_mut().spawn((
+            Transform::default(), PortalTransit { frame: Block::Basalt, destination }
+        )).id();
+        app.update();
+        assert!(app.world().get::<PortalTransit>(traveller).is_none());
+        assert!(app.world().get::<InPortal>(traveller).is_some());
+        let translation = app.world().get::<Transform>(traveller).unwrap().translation;
+        assert_eq!(ColPos::from((translation, Realm::Nether)), col);
+    }
+}
@@ -1,9 +1,10 @@
 use std::ops::{Add, BitXor};
 use bevy::prelude::Vec3;
+use serde::{Deserialize, Serialize};
 use crate::world::{Realm, CHUNK_S1};
 use super::{chunked, unchunked, ColPos, CHUNK_S1I};
 
-#[derive(Clone, Copy, Eq, PartialEq, Default, Debug, Hash)]
+#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Default, Debug, Hash, Serialize, Deserialize)]
 pub struct Pos3d<const U: usize> {
     pub x: i32,
     pub y: i32,
@@ -134,9 +135,9 @@ impl From<BlockPos> for (ColPos, (usize, i32, usize)) {
 
 impl From<BlockPos> for ChunkPos {
     fn from(block_pos: BlockPos) -> Self {
-        let cx = block_pos.x/CHUNK_S1I;
-        let cy = block_pos.y/CHUNK_S1I;
-        let cz = block_pos.z/CHUNK_S1I;
+        let cx = block_pos.x.div_euclid(CHUNK_S1I);
+        let cy = block_pos.y.div_euclid(CHUNK_S1I);
+        let cz = block_pos.z.div_euclid(CHUNK_S1I);
         ChunkPos {
             x: cx,
             y: cy,
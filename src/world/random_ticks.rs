@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use bevy::prelude::{Query, Res, ResMut, Resource};
+use itertools::iproduct;
+use rand::{Rng, RngCore};
+use crate::{Block, BlockFamily, WorldRng};
+use super::{BlockChangeCause, BlockPos, ChunkPos, ColPos, PlayerArea, VoxelWorld, WorldClock, CHUNK_S1I};
+
+/// Voxels picked at random in each chunk every game tick
+pub const RANDOM_TICKS_PER_CHUNK: usize = 3;
+/// Only the chunks this close (in columns) to a player get random ticks
+const RANDOM_TICK_DIST: i32 = 4;
+// game ticks caught up in one frame at most, so a long frame doesn't snowball
+const MAX_TICKS_PER_FRAME: u64 = 10;
+const LEAVES_REACH: i32 = 4;
+
+/// Called with the position of the voxel that got a random tick
+pub type RandomTickHandler = fn(&VoxelWorld, BlockPos, &mut dyn RngCore);
+
+/// Handlers of random ticks, registered by block or by block family
+#[derive(Resource, Default)]
+pub struct RandomTicks {
+    by_block: HashMap<Block, Vec<RandomTickHandler>>,
+    by_family: HashMap<BlockFamily, Vec<RandomTickHandler>>,
+    // last game tick that was processed
+    last_tick: Option<u64>,
+}
+
+impl RandomTicks {
+    pub fn register_block(&mut self, block: Block, handler: RandomTickHandler) -> &mut Self {
+        self.by_block.entry(block).or_default().push(handler);
+        self
+    }
+
+    pub fn register_family(&mut self, family: BlockFamily, handler: RandomTickHandler) -> &mut Self {
+        self.by_family.entry(family).or_default().push(handler);
+        self
+    }
+
+    /// Picks `count` random voxels in each chunk, in order, and runs the handlers of their block
+    pub fn tick_chunks(&self, world: &VoxelWorld, chunks: &[ChunkPos], count: usize, rng: &mut dyn RngCore) {
+        for chunk_pos in chunks {
+            for _ in 0..count {
+                let pos = BlockPos {
+                    x: chunk_pos.x*CHUNK_S1I + rng.gen_range(0..CHUNK_S1I),
+                    y: chunk_pos.y*CHUNK_S1I + rng.gen_range(0..CHUNK_S1I),
+                    z: chunk_pos.z*CHUNK_S1I + rng.gen_range(0..CHUNK_S1I),
+                    realm: chunk_pos.realm,
+                };
+                let block = world.get_block(pos);
+                let handlers = self.by_block.get(&block).into_iter()
+                    .chain(block.families().into_iter().filter_map(|family| self.by_family.get(&family)))
+                    .flatten();
+                for handler in handlers {
+                    handler(world, pos, rng);
+                }
+            }
+        }
+    }
+}
+
+/// Grass spreads to the dirt around it that has air above, and dies when covered
+pub fn spread_grass(world: &VoxelWorld, pos: BlockPos, rng: &mut dyn RngCore) {
+    if world.get_block(pos + (0, 1, 0)).is_opaque() {
+        world.set_block(pos, Block::Dirt, BlockChangeCause::Tick);
+        return;
+    }
+    let target = pos + (rng.gen_range(-1..=1), rng.gen_range(-3..=1), rng.gen_range(-1..=1));
+    if world.get_block(target) == Block::Dirt && world.get_block(target + (0, 1, 0)) == Block::Air {
+        world.set_block(target, Block::GrassBlock, BlockChangeCause::Tick);
+    }
+}
+
+/// Leaves that are too far from any log decay
+pub fn decay_leaves(world: &VoxelWorld, pos: BlockPos, _rng: &mut dyn RngCore) {
+    let reach = -LEAVES_REACH..=LEAVES_REACH;
+    let supported = iproduct!(reach.clone(), reach.clone(), reach)
+        .any(|offset| world.get_block(pos + offset).families().contains(&BlockFamily::Log));
+    if !supported {
+        world.set_block(pos, Block::Air, BlockChangeCause::Tick);
+    }
+}
+
+/// Random ticks happen in the loaded chunks close to a player, for every game tick that passed
+pub fn run_random_ticks(
+    world: Res<VoxelWorld>,
+    clock: Res<WorldClock>,
+    mut random_ticks: ResMut<RandomTicks>,
+    mut world_rng: ResMut<WorldRng>,
+    load_areas: Query<&PlayerArea>,
+) {
+    let last_tick = random_ticks.last_tick.unwrap_or(clock.tick);
+    random_ticks.last_tick = Some(clock.tick);
+    let ticks = (clock.tick - last_tick).min(MAX_TICKS_PER_FRAME);
+    if ticks == 0 {
+        return;
+    }
+    let centers: Vec<ColPos> = load_areas.iter().map(|load_area| load_area.center).collect();
+    // sorted so the ticks only depend on the rng
+    let mut chunks: Vec<ChunkPos> = world.chunks.iter()
+        .map(|entry| *entry.key())
+        .filter(|chunk_pos| {
+            let col_pos = ColPos::from(*chunk_pos);
+            centers.iter().any(|center| center.realm == col_pos.realm && center.dist(col_pos) <= RANDOM_TICK_DIST)
+        })
+        .collect();
+    chunks.sort();
+    for _ in 0..ticks {
+        random_ticks.tick_chunks(&world, &chunks, RANDOM_TICKS_PER_CHUNK, &mut world_rng.rng);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use itertools::iproduct;
+    use crate::{world::{BlockChangeCause, BlockPos, ChunkPos, Realm, VoxelWorld}, Block, BlockFamily};
+    use super::{decay_leaves, spread_grass, RandomTicks};
+
+    fn grass_world() -> VoxelWorld {
+        let world = VoxelWorld::new();
+        for (x, z) in iproduct!(0..10, 0..10) {
+            world.set_block(BlockPos { x, y: 0, z, realm: Realm::Overworld }, Block::Dirt, BlockChangeCause::Gen);
+        }
+        world.set_block(BlockPos { x: 5, y: 0, z: 5, realm: Realm::Overworld }, Block::GrassBlock, BlockChangeCause::Gen);
+        world.set_block(BlockPos { x: 5, y: 5, z: 5, realm: Realm::Overworld }, Block::OakLeaves, BlockChangeCause::Gen);
+        world
+    }
+
+    fn blocks(world: &VoxelWorld) -> Vec<Block> {
+        iproduct!(0..10, 0..10, 0..10).map(|(x, y, z)| world.get_block(BlockPos { x, y, z, realm: Realm::Overworld })).collect()
+    }
+
+    #[test]
+    fn test_random_ticks_are_deterministic() {
+        let mut random_ticks = RandomTicks::default();
+        random_ticks
+            .register_block(Block::GrassBlock, spread_grass)
+            .register_family(BlockFamily::Leaves, decay_leaves);
+        let chunks = [ChunkPos { x: 0, y: 0, z: 0, realm: Realm::Overworld }];
+        let results: Vec<Vec<Block>> = (0..2).map(|_| {
+            let world = grass_world();
+            let mut rng = ChaCha8Rng::seed_from_u64(42);
+            // only a few voxels of the chunk are ticked, a lot of ticks are needed to hit the blocks we placed
+            for _ in 0..200_000 {
+                random_ticks.tick_chunks(&world, &chunks, 3, &mut rng);
+            }
+            blocks(&world)
+        }).collect();
+        assert_eq!(results[0], results[1]);
+        assert!(results[0].iter().filter(|block| **block == Block::GrassBlock).count() > 1);
+        assert!(!results[0].contains(&Block::OakLeaves));
+    }
+}
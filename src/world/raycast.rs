@@ -0,0 +1,135 @@
+use bevy::prelude::Vec3;
+use crate::{block::Face, Block};
+use super::{BlockPos, Realm, VoxelWorld};
+
+pub struct BlockRayCastHit {
+    pub pos: BlockPos,
+    pub block: Block,
+    /// Normal of the face through which the ray entered the block
+    pub normal: Vec3,
+    pub face: Face,
+    /// Where the ray entered the block
+    pub point: Vec3,
+    /// Distance from the start of the ray to `point`
+    pub dist: f32,
+}
+
+impl PartialEq for BlockRayCastHit {
+    fn eq(&self, other: &Self) -> bool {
+        self.pos == other.pos
+    }
+}
+
+/// Iterates over every block crossed by a ray, in order (the block containing the start of the ray is not included).
+/// Uses the voxel traversal algorithm from "A Fast Voxel Traversal Algorithm for Ray Tracing" (Amanatides & Woo).
+pub struct RayCast<'w> {
+    world: &'w VoxelWorld,
+    start: Vec3,
+    dir: Vec3,
+    max_dist: f32,
+    pos: BlockPos,
+    step: [i32; 3],
+    // distance along the ray at which the next block boundary is crossed, on each axis
+    t_max: [f32; 3],
+    // distance along the ray between 2 block boundaries, on each axis
+    t_delta: [f32; 3],
+}
+
+impl<'w> Iterator for RayCast<'w> {
+    type Item = BlockRayCastHit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let axis = (0..3).min_by(|a, b| self.t_max[*a].total_cmp(&self.t_max[*b]))?;
+        let dist = self.t_max[axis];
+        if dist >= self.max_dist {
+            return None;
+        }
+        match axis {
+            0 => self.pos.x += self.step[0],
+            1 => self.pos.y += self.step[1],
+            _ => self.pos.z += self.step[2],
+        }
+        self.t_max[axis] += self.t_delta[axis];
+        let mut normal = Vec3::ZERO;
+        normal[axis] = -self.step[axis] as f32;
+        let face = match (axis, self.step[axis] > 0) {
+            (0, true) => Face::Left,
+            (0, false) => Face::Right,
+            (1, true) => Face::Down,
+            (1, false) => Face::Up,
+            (_, true) => Face::Back,
+            (_, false) => Face::Front,
+        };
+        Some(BlockRayCastHit {
+            pos: self.pos,
+            block: self.world.get_block_safe(self.pos),
+            normal,
+            face,
+            point: self.start + self.dir * dist,
+            dist,
+        })
+    }
+}
+
+impl VoxelWorld {
+    /// Every block crossed by the ray within `dist`, for line of sight and projectiles
+    pub fn raycast_iter(&self, realm: Realm, start: Vec3, dir: Vec3, dist: f32) -> RayCast<'_> {
+        let dir = dir.normalize_or_zero();
+        let pos = BlockPos {
+            realm,
+            x: start.x.floor() as i32,
+            y: start.y.floor() as i32,
+            z: start.z.floor() as i32,
+        };
+        let mut step = [0; 3];
+        let mut t_max = [f32::INFINITY; 3];
+        let mut t_delta = [f32::INFINITY; 3];
+        let block_start = [pos.x as f32, pos.y as f32, pos.z as f32];
+        for axis in 0..3 {
+            if dir[axis] == 0. {
+                continue;
+            }
+            step[axis] = dir[axis].signum() as i32;
+            let next_boundary = block_start[axis] + step[axis].max(0) as f32;
+            t_max[axis] = (next_boundary - start[axis]) / dir[axis];
+            t_delta[axis] = 1. / dir[axis].abs();
+        }
+        RayCast { world: self, start, dir, max_dist: dist, pos, step, t_max, t_delta }
+    }
+
+    /// The first block crossed by the ray within `dist` for which `stop` returns true
+    pub fn raycast_with(
+        &self, realm: Realm, start: Vec3, dir: Vec3, dist: f32, stop: impl Fn(Block) -> bool
+    ) -> Option<BlockRayCastHit> {
+        self.raycast_iter(realm, start, dir, dist).find(|hit| stop(hit.block))
+    }
+
+    /// The first targetable block crossed by the ray within `dist`
+    pub fn raycast(&self, realm: Realm, start: Vec3, dir: Vec3, dist: f32) -> Option<BlockRayCastHit> {
+        self.raycast_with(realm, start, dir, dist, |block| block.is_targetable())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Vec3;
+    use crate::{block::Face, world::{BlockChangeCause, BlockPos, Realm, VoxelWorld}, Block};
+
+    #[test]
+    fn test_raycast_hit() {
+        let world = VoxelWorld::new();
+        let target = BlockPos { x: 3, y: 10, z: 0, realm: Realm::Overworld };
+        world.set_block(target, Block::Granite, BlockChangeCause::Gen);
+        world.set_block(target + (-1, 0, 0), Block::OakLeaves, BlockChangeCause::Gen);
+        let start = Vec3::new(0.5, 10.5, 0.5);
+        let hit = world.raycast_with(Realm::Overworld, start, Vec3::X, 10., |block| block != Block::Air && !block.is_foliage()).unwrap();
+        assert_eq!(hit.pos, target);
+        assert_eq!(hit.face, Face::Left);
+        assert_eq!(hit.normal, Vec3::new(-1., 0., 0.));
+        assert!((hit.dist - 2.5).abs() < 1e-5);
+        assert!((hit.point - Vec3::new(3., 10.5, 0.5)).length() < 1e-5);
+        let crossed = world.raycast_iter(Realm::Overworld, start, Vec3::X, 10.).take_while(|hit| hit.pos != target).count();
+        assert_eq!(crossed, 2);
+        assert!(world.raycast_with(Realm::Overworld, start, Vec3::X, 2., |block| block == Block::Granite).is_none());
+    }
+}
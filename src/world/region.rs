@@ -0,0 +1,235 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use bevy::prelude::Resource;
+use parking_lot::Mutex;
+use super::{
+    pos2d::chunks_in_col, Chunk, ChunkPos, ColPos, TrackedChunk, VoxelWorld,
+};
+
+/// A region file holds REGION_S1 x REGION_S1 columns
+const REGION_S1: i32 = 32;
+const REGION_S2: usize = (REGION_S1 * REGION_S1) as usize;
+const SECTOR_SIZE: u64 = 4096;
+// for each column of the region: (offset in sectors, length in bytes)
+const HEADER_ENTRY_SIZE: u64 = 8;
+const HEADER_SIZE: u64 = REGION_S2 as u64 * HEADER_ENTRY_SIZE;
+const HEADER_SECTORS: u64 = HEADER_SIZE.div_ceil(SECTOR_SIZE);
+
+/// Stores edited columns on disk, grouped in region files.
+/// Columns are written in 4KB sectors, rewritten in place when they still fit,
+/// and appended at the end of the file otherwise.
+#[derive(Resource, Clone)]
+pub struct Regions {
+    path: PathBuf,
+    // file operations are serialized since both the gen thread and the main thread use the regions
+    lock: Arc<Mutex<()>>,
+}
+
+fn region_of(col_pos: ColPos) -> ((i32, i32), usize) {
+    let (rx, dx) = (col_pos.x.div_euclid(REGION_S1), col_pos.x.rem_euclid(REGION_S1));
+    let (rz, dz) = (col_pos.z.div_euclid(REGION_S1), col_pos.z.rem_euclid(REGION_S1));
+    ((rx, rz), (dx * REGION_S1 + dz) as usize)
+}
+
+impl Regions {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Regions {
+            path: path.as_ref().to_path_buf(),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    fn region_path(&self, col_pos: ColPos) -> (PathBuf, usize) {
+        let ((rx, rz), i) = region_of(col_pos);
+        let realm = format!("{:?}", col_pos.realm).to_lowercase();
+        (self.path.join(realm).join(format!("r.{rx}.{rz}.bin")), i)
+    }
+
+    fn read_col_bytes(&self, col_pos: ColPos) -> io::Result<Option<Vec<u8>>> {
+        let (path, i) = self.region_path(col_pos);
+        let _guard = self.lock.lock();
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let (offset, len) = read_header_entry(&mut file, i)?;
+        if offset == 0 {
+            return Ok(None);
+        }
+        let mut bytes = vec![0; len as usize];
+        file.seek(SeekFrom::Start(offset as u64 * SECTOR_SIZE))?;
+        file.read_exact(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+
+    fn write_col_bytes(&self, col_pos: ColPos, bytes: &[u8]) -> io::Result<()> {
+        let (path, i) = self.region_path(col_pos);
+        let _guard = self.lock.lock();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().read(true).write(true).create(true).open(&path)?;
+        if file.metadata()?.len() < HEADER_SECTORS * SECTOR_SIZE {
+            file.set_len(HEADER_SECTORS * SECTOR_SIZE)?;
+        }
+        let (old_offset, old_len) = read_header_entry(&mut file, i)?;
+        let sectors = (bytes.len() as u64).div_ceil(SECTOR_SIZE);
+        let offset = if old_offset != 0 && (old_len as u64).div_ceil(SECTOR_SIZE) >= sectors {
+            old_offset as u64
+        } else {
+            // NOTE: the old sectors are leaked, region files are never compacted for now
+            file.metadata()?.len().div_ceil(SECTOR_SIZE)
+        };
+        file.seek(SeekFrom::Start(offset * SECTOR_SIZE))?;
+        file.write_all(bytes)?;
+        let padding = sectors * SECTOR_SIZE - bytes.len() as u64;
+        file.write_all(&vec![0; padding as usize])?;
+        file.seek(SeekFrom::Start(i as u64 * HEADER_ENTRY_SIZE))?;
+        file.write_all(&(offset as u32).to_le_bytes())?;
+        file.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the column to disk if any of its chunks were edited since it was generated or loaded.
+    /// Columns that aren't loaded are skipped, their chunks only hold what spilled from their neighbours
+    /// (like tree leaves) and saving them would prevent their generation.
+    pub fn save_col(&self, world: &VoxelWorld, col_pos: ColPos) {
+        if !world.heightmaps.contains_key(&col_pos) {
+            return;
+        }
+        let chunk_positions = chunks_in_col(&col_pos);
+        let edited = chunk_positions.iter().any(|chunk_pos|
+            world.chunks.get(chunk_pos).is_some_and(|chunk| chunk.edited)
+        );
+        if !edited {
+            return;
+        }
+        let mut bytes = Vec::new();
+        for (y, chunk_pos) in chunk_positions.iter().enumerate() {
+            let Some(chunk) = world.chunks.get(chunk_pos) else {
+                continue;
+            };
+            let chunk_bytes = chunk.encode();
+            bytes.push(y as u8);
+            bytes.extend_from_slice(&(chunk_bytes.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&chunk_bytes);
+        }
+        if let Err(err) = self.write_col_bytes(col_pos, &bytes) {
+            // the chunks stay edited so the column is saved again on the next try
+            println!("couldn't save column {:?}: {}", col_pos, err);
+            return;
+        }
+        for chunk_pos in chunk_positions.iter() {
+            if let Some(mut chunk) = world.chunks.get_mut(chunk_pos) {
+                chunk.edited = false;
+            }
+        }
+    }
+
+    /// Loads the column from disk into the world, returns false if the column was never saved
+    pub fn load_col(&self, world: &VoxelWorld, col_pos: ColPos) -> bool {
+        let bytes = match self.read_col_bytes(col_pos) {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => return false,
+            Err(err) => {
+                println!("couldn't load column {:?}: {}", col_pos, err);
+                return false;
+            }
+        };
+        let chunk_positions = chunks_in_col(&col_pos);
+        let mut reader = bytes.as_slice();
+        let mut chunks = Vec::new();
+        while !reader.is_empty() {
+            let Some((chunk_pos, chunk)) = read_chunk_entry(&mut reader, &chunk_positions) else {
+                println!("column {:?} is corrupted on disk", col_pos);
+                return false;
+            };
+            chunks.push((chunk_pos, chunk));
+        }
+        for (chunk_pos, chunk) in chunks {
+            world.chunks.insert(chunk_pos, TrackedChunk::from(chunk));
+        }
+        true
+    }
+}
+
+fn read_header_entry(file: &mut File, i: usize) -> io::Result<(u32, u32)> {
+    let mut entry = [0; HEADER_ENTRY_SIZE as usize];
+    file.seek(SeekFrom::Start(i as u64 * HEADER_ENTRY_SIZE))?;
+    file.read_exact(&mut entry)?;
+    Ok((
+        u32::from_le_bytes(entry[0..4].try_into().unwrap()),
+        u32::from_le_bytes(entry[4..8].try_into().unwrap()),
+    ))
+}
+
+fn read_chunk_entry(reader: &mut &[u8], chunk_positions: &[ChunkPos]) -> Option<(ChunkPos, Chunk)> {
+    let y = read_u8(reader)? as usize;
+    let len = read_u32(reader)? as usize;
+    let chunk_bytes = reader.get(..len)?;
+    *reader = &reader[len..];
+    Some((*chunk_positions.get(y)?, Chunk::decode(chunk_bytes).ok()?))
+}
+
+fn read_u8(reader: &mut &[u8]) -> Option<u8> {
+    let (value, rest) = reader.split_first()?;
+    *reader = rest;
+    Some(*value)
+}
+
+fn read_u32(reader: &mut &[u8]) -> Option<u32> {
+    let bytes = reader.get(..4)?;
+    *reader = &reader[4..];
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{world::{BlockChangeCause, BlockPos, ChunkPos, ColPos, Realm, VoxelWorld}, Block};
+    use super::Regions;
+
+    #[test]
+    fn test_spilled_cols_are_not_saved() {
+        let path = std::env::temp_dir().join(format!("riverbed_regions_{}", std::process::id()));
+        let regions = Regions::new(&path);
+        let world = VoxelWorld::new();
+        let loaded = ColPos { x: 0, z: 0, realm: Realm::Overworld };
+        let unloaded = ColPos { x: -1, z: 0, realm: Realm::Overworld };
+        world.set_yrange(loaded, (10, 10), 60, 1, Block::Dirt);
+        world.compute_heightmaps(loaded);
+        // a tree of the loaded column grows leaves in its unloaded neighbour
+        for x in [-1, 1] {
+            world.set_if_empty(BlockPos { x, y: 65, z: 10, realm: Realm::Overworld }, Block::OakLeaves, BlockChangeCause::Gen);
+        }
+        regions.save_col(&world, loaded);
+        regions.save_col(&world, unloaded);
+        let reloaded = VoxelWorld::new();
+        assert!(regions.load_col(&reloaded, loaded));
+        assert!(!regions.load_col(&reloaded, unloaded));
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_failed_saves_are_retried() {
+        let path = std::env::temp_dir().join(format!("riverbed_regions_retry_{}", std::process::id()));
+        // the regions can't be written under a file
+        std::fs::write(&path, []).unwrap();
+        let world = VoxelWorld::new();
+        let col = ColPos { x: 0, z: 0, realm: Realm::Overworld };
+        world.set_yrange(col, (10, 10), 60, 1, Block::Dirt);
+        world.compute_heightmaps(col);
+        world.set_block(BlockPos { x: 3, y: 10, z: 3, realm: Realm::Overworld }, Block::Granite, BlockChangeCause::Gen);
+        let chunk_pos = ChunkPos { x: 0, y: 0, z: 0, realm: Realm::Overworld };
+        Regions::new(path.join("regions")).save_col(&world, col);
+        assert!(world.chunks.get(&chunk_pos).unwrap().edited);
+        std::fs::remove_file(&path).unwrap();
+        Regions::new(&path).save_col(&world, col);
+        assert!(!world.chunks.get(&chunk_pos).unwrap().edited);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::{HashMap, HashSet};
+use crate::{items::BlockKind, Block};
+use super::{BlockChangeCause, BlockChanged, BlockPos, ChunkPos, ChunkedPos, VoxelWorld, MAX_HEIGHT, Y_CHUNKS};
+
+/// A set of blocks to edit at once
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditShape {
+    /// Axis aligned box between 2 corners (included)
+    Cuboid(BlockPos, BlockPos),
+    /// Only the faces of the box between 2 corners
+    HollowCuboid(BlockPos, BlockPos),
+    Sphere { center: BlockPos, radius: f32 },
+    /// Vertical cylinder, starting at `base` and going up
+    Cylinder { base: BlockPos, radius: f32, height: i32 },
+    Line(BlockPos, BlockPos),
+}
+
+/// What to do with the blocks of an EditShape
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditOp {
+    Fill(Block),
+    /// Only replaces the blocks matching the kind
+    Replace(BlockKind, Block),
+}
+
+impl EditOp {
+    fn apply(&self, block: Block) -> Block {
+        match self {
+            EditOp::Fill(new_block) => *new_block,
+            EditOp::Replace(kind, new_block) if kind.matches(&block) => *new_block,
+            EditOp::Replace(..) => block,
+        }
+    }
+}
+
+fn min_max(a: BlockPos, b: BlockPos) -> (BlockPos, BlockPos) {
+    (
+        BlockPos { x: a.x.min(b.x), y: a.y.min(b.y), z: a.z.min(b.z), realm: a.realm },
+        BlockPos { x: a.x.max(b.x), y: a.y.max(b.y), z: a.z.max(b.z), realm: a.realm },
+    )
+}
+
+fn cuboid(a: BlockPos, b: BlockPos, hollow: bool) -> Vec<BlockPos> {
+    let (min, max) = min_max(a, b);
+    let mut res = Vec::new();
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            for z in min.z..=max.z {
+                let on_border = x == min.x || x == max.x || y == min.y || y == max.y || z == min.z || z == max.z;
+                if !hollow || on_border {
+                    res.push(BlockPos { x, y, z, realm: a.realm });
+                }
+            }
+        }
+    }
+    res
+}
+
+impl EditShape {
+    pub fn positions(&self) -> Vec<BlockPos> {
+        match *self {
+            EditShape::Cuboid(a, b) => cuboid(a, b, false),
+            EditShape::HollowCuboid(a, b) => cuboid(a, b, true),
+            EditShape::Sphere { center, radius } => {
+                let r = radius.floor() as i32;
+                cuboid(center + (-r, -r, -r), center + (r, r, r), false).into_iter().filter(|pos| {
+                    let (dx, dy, dz) = ((pos.x - center.x) as f32, (pos.y - center.y) as f32, (pos.z - center.z) as f32);
+                    dx*dx + dy*dy + dz*dz <= radius*radius
+                }).collect()
+            },
+            EditShape::Cylinder { base, radius, height } => {
+                let r = radius.floor() as i32;
+                cuboid(base + (-r, 0, -r), base + (r, height-1, r), false).into_iter().filter(|pos| {
+                    let (dx, dz) = ((pos.x - base.x) as f32, (pos.z - base.z) as f32);
+                    dx*dx + dz*dz <= radius*radius
+                }).collect()
+            },
+            EditShape::Line(a, b) => {
+                let (dx, dy, dz) = (b.x - a.x, b.y - a.y, b.z - a.z);
+                let steps = dx.abs().max(dy.abs()).max(dz.abs());
+                if steps == 0 {
+                    return vec![a];
+                }
+                (0..=steps).map(|i| {
+                    let t = i as f32 / steps as f32;
+                    BlockPos {
+                        x: a.x + (dx as f32 * t).round() as i32,
+                        y: a.y + (dy as f32 * t).round() as i32,
+                        z: a.z + (dz as f32 * t).round() as i32,
+                        realm: a.realm,
+                    }
+                }).collect()
+            }
+        }
+    }
+}
+
+impl VoxelWorld {
+    /// Applies `op` to every block of `shape`, returns the number of blocks that changed.
+    pub fn edit(&self, shape: EditShape, op: EditOp, cause: BlockChangeCause) -> usize {
+        self.edit_with(shape.positions(), |_, block| op.apply(block), cause)
+    }
+
+    /// Replaces each block at `positions` by `edit(pos, block)`, returns the number of blocks that changed.
+    /// Writes are batched per chunk and each touched chunk (and its border neighbours) is marked as changed once.
+    /// Blocks in chunks that aren't loaded are skipped.
+    pub fn edit_with(
+        &self, positions: impl IntoIterator<Item = BlockPos>, edit: impl Fn(BlockPos, Block) -> Block, cause: BlockChangeCause
+    ) -> usize {
+        let mut per_chunk: HashMap<ChunkPos, Vec<(BlockPos, ChunkedPos)>> = HashMap::new();
+        for pos in positions {
+            if pos.y < 0 || pos.y >= MAX_HEIGHT as i32 {
+                continue;
+            }
+            let (chunk_pos, chunked_pos) = <(ChunkPos, ChunkedPos)>::from(pos);
+            per_chunk.entry(chunk_pos).or_default().push((pos, chunked_pos));
+        }
+        let mut changes = Vec::new();
+        let mut neighbours = HashSet::new();
+        for (chunk_pos, positions) in per_chunk {
+            let Some(mut chunk) = self.chunks.get_mut(&chunk_pos) else {
+                continue;
+            };
+            let changes_before = changes.len();
+            for (pos, chunked_pos) in positions {
+                let old_block = *chunk.get(chunked_pos);
+                let new_block = edit(pos, old_block);
+                if old_block == new_block {
+                    continue;
+                }
+                chunk.set(chunked_pos, new_block);
+                changes.push(BlockChanged { pos, old_block, new_block, cause });
+                neighbours.extend(VoxelWorld::border_neighbours(chunk_pos, chunked_pos));
+            }
+            if changes.len() > changes_before {
+                chunk.changed = true;
+                chunk.edited = true;
+            }
+        }
+        for neighbour in neighbours {
+            if neighbour.y >= 0 && neighbour.y < Y_CHUNKS as i32 {
+                self.mark_change_single(neighbour);
+            }
+        }
+        let count = changes.len();
+        // the chunks are all written by now so the heightmaps can look below removed blocks
+        for change in changes.iter() {
+            self.update_heightmaps(change.pos, change.new_block);
+        }
+        for change in changes {
+            self.publish_change(change.pos, change.old_block, change.new_block, change.cause);
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::{BlockPos2d, ColPos, Heightmap, Realm};
+    use super::*;
+
+    #[test]
+    fn test_shape_positions() {
+        let origin = BlockPos { x: 0, y: 0, z: 0, realm: Realm::Overworld };
+        assert_eq!(EditShape::Cuboid(origin, origin + (2, 2, 2)).positions().len(), 27);
+        assert_eq!(EditShape::HollowCuboid(origin, origin + (2, 2, 2)).positions().len(), 26);
+        let line = EditShape::Line(origin, origin + (5, -2, 1)).positions();
+        assert_eq!(line.len(), 6);
+        assert_eq!(line.last(), Some(&(origin + (5, -2, 1))));
+        assert_eq!(EditShape::Sphere { center: origin, radius: 1. }.positions().len(), 7);
+    }
+
+    #[test]
+    fn test_edits_update_heightmaps() {
+        let world = VoxelWorld::new();
+        let col = ColPos { x: 0, z: 0, realm: Realm::Overworld };
+        let pos2d = BlockPos2d { x: 5, z: 5, realm: Realm::Overworld };
+        world.set_yrange(col, (5, 5), 40, 10, Block::Granite);
+        world.compute_heightmaps(col);
+        let top = BlockPos { x: 5, y: 40, z: 5, realm: Realm::Overworld };
+        world.edit(EditShape::Cuboid(top + (0, -5, 0), top), EditOp::Fill(Block::Air), BlockChangeCause::Player);
+        assert_eq!(world.height(pos2d, Heightmap::Solid), Some(35));
+        world.edit(EditShape::Line(top, top + (0, 20, 0)), EditOp::Fill(Block::Glass), BlockChangeCause::Player);
+        assert_eq!(world.height(pos2d, Heightmap::Solid), Some(61));
+        assert_eq!(world.height(pos2d, Heightmap::Opaque), Some(35));
+    }
+}
@@ -0,0 +1,267 @@
+use std::{fs, io, path::Path, str::FromStr};
+use itertools::{iproduct, Itertools};
+use packed_uints::PackedUints;
+use crate::Block;
+use super::{utils::{read_varint, write_varint, Palette}, BlockChangeCause, BlockPos, VoxelWorld};
+
+const SCHEMATIC_VERSION: u8 = 1;
+/// Schematics bigger than this are rejected when decoding, it's a 256 blocks wide cube
+const MAX_VOLUME: usize = 1 << 24;
+
+/// A box of blocks copied from the world, that can be transformed, saved and pasted elsewhere.
+#[derive(Debug)]
+pub struct Schematic {
+    /// Size along x, y and z
+    pub size: [usize; 3],
+    /// Position of the paste anchor, relative to the lowest corner of the box
+    pub origin: [i32; 3],
+    palette: Palette<Block>,
+    data: PackedUints,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SchematicDecodeError {
+    UnknownVersion(u8),
+    Truncated,
+    BadPaletteIndex(usize),
+    WrongVoxelCount(usize),
+    TooBig([usize; 3]),
+}
+
+fn zigzag(value: i32) -> usize {
+    ((value << 1) ^ (value >> 31)) as u32 as usize
+}
+
+fn unzigzag(value: usize) -> i32 {
+    ((value >> 1) as i32) ^ -((value & 1) as i32)
+}
+
+impl Schematic {
+    fn index(&self, [x, y, z]: [usize; 3]) -> usize {
+        z + x * self.size[2] + y * self.size[0] * self.size[2]
+    }
+
+    // (x, y, z) positions of the schematic, in the same order as data
+    fn positions(size: [usize; 3]) -> impl Iterator<Item = [usize; 3]> {
+        iproduct!(0..size[1], 0..size[0], 0..size[2]).map(|(y, x, z)| [x, y, z])
+    }
+
+    fn from_blocks(size: [usize; 3], origin: [i32; 3], blocks: impl Iterator<Item = Block>) -> Self {
+        let mut palette = Palette::new();
+        palette.index(Block::Air);
+        let values = blocks.map(|block| palette.index(block)).collect_vec();
+        Schematic { size, origin, palette, data: PackedUints::from(values.as_slice()) }
+    }
+
+    /// Copies the box between corners `a` and `b` (included), `origin` is the anchor used when pasting
+    pub fn copy(world: &VoxelWorld, a: BlockPos, b: BlockPos, origin: BlockPos) -> Self {
+        let min = [a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)];
+        let size = [
+            a.x.abs_diff(b.x) as usize + 1,
+            a.y.abs_diff(b.y) as usize + 1,
+            a.z.abs_diff(b.z) as usize + 1,
+        ];
+        let blocks = Schematic::positions(size).map(|[x, y, z]| world.get_block_safe(BlockPos {
+            x: min[0] + x as i32,
+            y: min[1] + y as i32,
+            z: min[2] + z as i32,
+            realm: a.realm,
+        }));
+        Schematic::from_blocks(size, [origin.x - min[0], origin.y - min[1], origin.z - min[2]], blocks)
+    }
+
+    pub fn get(&self, pos: [usize; 3]) -> Block {
+        self.palette[self.data.get(self.index(pos))]
+    }
+
+    /// Pastes the schematic with its origin at `at`, air blocks of the schematic are skipped unless `with_air` is true.
+    /// Returns the number of blocks that changed.
+    pub fn paste(&self, world: &VoxelWorld, at: BlockPos, with_air: bool, cause: BlockChangeCause) -> usize {
+        let min = at + (-self.origin[0], -self.origin[1], -self.origin[2]);
+        let world_pos = |[x, y, z]: [usize; 3]| min + (x as i32, y as i32, z as i32);
+        world.edit_with(
+            Schematic::positions(self.size).filter(|pos| with_air || self.get(*pos) != Block::Air).map(world_pos),
+            |pos, _| self.get([(pos.x - min.x) as usize, (pos.y - min.y) as usize, (pos.z - min.z) as usize]),
+            cause
+        )
+    }
+
+    // Applies an affine transformation that maps the box [0, size) onto [0, new_size)
+    fn transformed(&self, new_size: [usize; 3], transform: impl Fn([i32; 3]) -> [i32; 3]) -> Self {
+        let mut values = vec![0; self.size.iter().product()];
+        let new_index = |[x, y, z]: [i32; 3]| z as usize + x as usize * new_size[2] + y as usize * new_size[0] * new_size[2];
+        for pos in Schematic::positions(self.size) {
+            let new_pos = transform([pos[0] as i32, pos[1] as i32, pos[2] as i32]);
+            values[new_index(new_pos)] = self.data.get(self.index(pos));
+        }
+        let mut palette = Palette::new();
+        for block in self.palette.iter() {
+            palette.index(*block);
+        }
+        Schematic {
+            size: new_size,
+            origin: transform(self.origin),
+            palette,
+            data: PackedUints::from(values.as_slice()),
+        }
+    }
+
+    /// Rotates the schematic by a quarter turn around the Y axis, clockwise seen from above
+    pub fn rotate_y(&self) -> Self {
+        let [sx, sy, sz] = self.size;
+        self.transformed([sz, sy, sx], |[x, y, z]| [sz as i32 - 1 - z, y, x])
+    }
+
+    pub fn mirror_x(&self) -> Self {
+        let sx = self.size[0] as i32;
+        self.transformed(self.size, |[x, y, z]| [sx - 1 - x, y, z])
+    }
+
+    pub fn mirror_z(&self) -> Self {
+        let sz = self.size[2] as i32;
+        self.transformed(self.size, |[x, y, z]| [x, y, sz - 1 - z])
+    }
+
+    /// `version: u8 | size | origin | palette length | (name length, block name)* | (run length, palette index)*`,
+    /// all as varints after the version (origin is zigzag encoded).
+    /// Blocks are stored by name so schematics can be shared between worlds.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![SCHEMATIC_VERSION];
+        for size in self.size {
+            write_varint(&mut bytes, size);
+        }
+        for origin in self.origin {
+            write_varint(&mut bytes, zigzag(origin));
+        }
+        write_varint(&mut bytes, self.palette.len());
+        for block in self.palette.iter() {
+            let name = block.to_string();
+            write_varint(&mut bytes, name.len());
+            bytes.extend_from_slice(name.as_bytes());
+        }
+        let values = self.data.unpack_u16();
+        for run in values.chunk_by(|a, b| a == b) {
+            write_varint(&mut bytes, run.len());
+            write_varint(&mut bytes, run[0] as usize);
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, SchematicDecodeError> {
+        let (version, mut reader) = bytes.split_first().ok_or(SchematicDecodeError::Truncated)?;
+        if *version != SCHEMATIC_VERSION {
+            return Err(SchematicDecodeError::UnknownVersion(*version));
+        }
+        let mut read = || read_varint(&mut reader).ok_or(SchematicDecodeError::Truncated);
+        let size = [read()?, read()?, read()?];
+        let origin = [unzigzag(read()?), unzigzag(read()?), unzigzag(read()?)];
+        let mut palette = Palette::new();
+        palette.index(Block::Air);
+        let mut remap = Vec::new();
+        for _ in 0..read_varint(&mut reader).ok_or(SchematicDecodeError::Truncated)? {
+            let len = read_varint(&mut reader).ok_or(SchematicDecodeError::Truncated)?;
+            let name = reader.get(..len).ok_or(SchematicDecodeError::Truncated)?;
+            reader = &reader[len..];
+            // blocks that don't exist anymore are replaced by air
+            let block = std::str::from_utf8(name).ok()
+                .and_then(|name| Block::from_str(name).ok())
+                .unwrap_or(Block::Air);
+            remap.push(palette.index(block));
+        }
+        // the size comes from the file too, it's checked before anything is allocated from it
+        let volume = size.iter()
+            .try_fold(1usize, |volume, side| volume.checked_mul(*side))
+            .filter(|volume| *volume <= MAX_VOLUME)
+            .ok_or(SchematicDecodeError::TooBig(size))?;
+        let mut values = Vec::new();
+        while !reader.is_empty() {
+            let len = read_varint(&mut reader).ok_or(SchematicDecodeError::Truncated)?;
+            let palette_i = read_varint(&mut reader).ok_or(SchematicDecodeError::Truncated)?;
+            let value = *remap.get(palette_i).ok_or(SchematicDecodeError::BadPaletteIndex(palette_i))?;
+            // the run length comes from the file, check it before expanding the run
+            if values.len().saturating_add(len) > volume {
+                return Err(SchematicDecodeError::WrongVoxelCount(values.len().saturating_add(len)));
+            }
+            values.extend(std::iter::repeat(value).take(len));
+        }
+        if values.len() != volume {
+            return Err(SchematicDecodeError::WrongVoxelCount(values.len()));
+        }
+        Ok(Schematic { size, origin, palette, data: PackedUints::from(values.as_slice()) })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.encode())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Schematic::decode(&fs::read(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Block;
+    use super::{write_varint, Schematic, SchematicDecodeError, SCHEMATIC_VERSION};
+
+    fn line() -> Schematic {
+        Schematic::from_blocks([3, 1, 2], [1, 0, 0], [
+            Block::OakLog, Block::Air, Block::Granite, Block::Air, Block::Dirt, Block::Air
+        ].into_iter())
+    }
+
+    #[test]
+    fn test_codec_roundtrip() {
+        let schematic = line();
+        let decoded = Schematic::decode(&schematic.encode()).unwrap();
+        assert_eq!(decoded.size, schematic.size);
+        assert_eq!(decoded.origin, schematic.origin);
+        for pos in Schematic::positions(schematic.size) {
+            assert_eq!(decoded.get(pos), schematic.get(pos));
+        }
+    }
+
+    #[test]
+    fn test_decode_rejects_long_runs() {
+        let mut bytes = line().encode();
+        // a run far longer than the schematic must be rejected before it's expanded
+        write_varint(&mut bytes, usize::MAX / 2);
+        write_varint(&mut bytes, 0);
+        assert!(matches!(Schematic::decode(&bytes), Err(SchematicDecodeError::WrongVoxelCount(_))));
+    }
+
+    #[test]
+    fn test_decode_rejects_huge_sizes() {
+        let mut bytes = vec![SCHEMATIC_VERSION];
+        // size, origin and an empty palette, the volume overflows
+        for value in [usize::MAX / 2, 4, 1, 0, 0, 0, 0] {
+            write_varint(&mut bytes, value);
+        }
+        assert_eq!(Schematic::decode(&bytes).unwrap_err(), SchematicDecodeError::TooBig([usize::MAX / 2, 4, 1]));
+        let mut bytes = vec![SCHEMATIC_VERSION];
+        for value in [1 << 10, 1 << 10, 1 << 10, 0, 0, 0, 0] {
+            write_varint(&mut bytes, value);
+        }
+        assert_eq!(Schematic::decode(&bytes).unwrap_err(), SchematicDecodeError::TooBig([1 << 10; 3]));
+    }
+
+    #[test]
+    fn test_rotations() {
+        let schematic = line();
+        let rotated = schematic.rotate_y();
+        assert_eq!(rotated.size, [2, 1, 3]);
+        // x = 0, z = 0 ends up at x = sz-1, z = 0
+        assert_eq!(rotated.get([1, 0, 0]), Block::OakLog);
+        let full_turn = rotated.rotate_y().rotate_y().rotate_y();
+        assert_eq!(full_turn.origin, schematic.origin);
+        for pos in Schematic::positions(schematic.size) {
+            assert_eq!(full_turn.get(pos), schematic.get(pos));
+        }
+        assert_eq!(schematic.mirror_x().get([2, 0, 0]), Block::OakLog);
+        assert_eq!(schematic.mirror_z().get([0, 0, 1]), Block::OakLog);
+    }
+}
@@ -1,4 +1,4 @@
 mod palette;
-mod reinsert;
+mod varint;
 pub use palette::*;
-pub use reinsert::*;
\ No newline at end of file
+pub use varint::*;
\ No newline at end of file
@@ -10,6 +10,10 @@ impl<E: Hash> Palette<E> {
     pub fn iter(&self) -> Iter<'_, E> {
         self.rightmap.iter()
     }
+
+    pub fn len(&self) -> usize {
+        self.rightmap.len()
+    }
 }
 
 impl<E: Hash + Eq + PartialEq + Clone> Palette<E> {
@@ -17,6 +21,10 @@ impl<E: Hash + Eq + PartialEq + Clone> Palette<E> {
         Self { leftmap: HashMap::new(), rightmap: Vec::new() }
     }
 
+    pub fn contains(&self, elem: &E) -> bool {
+        self.leftmap.contains_key(elem)
+    }
+
     pub fn index(&mut self, elem: E) -> usize {
         *self.leftmap.entry(elem.clone()).or_insert_with(|| {
             self.rightmap.push(elem);
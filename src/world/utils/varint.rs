@@ -0,0 +1,45 @@
+/// LEB128 unsigned varints, used by the chunk codec and schematic files
+pub fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Returns None if the reader ends before the varint does, or if the varint doesn't fit in a usize
+pub fn read_varint(reader: &mut &[u8]) -> Option<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        if shift >= usize::BITS {
+            return None;
+        }
+        let (byte, rest) = reader.split_first()?;
+        *reader = rest;
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_varint, write_varint};
+
+    #[test]
+    fn test_varint_roundtrip() {
+        let mut bytes = Vec::new();
+        for value in [0, 127, 128, 300, usize::MAX] {
+            write_varint(&mut bytes, value);
+        }
+        let mut reader = bytes.as_slice();
+        for value in [0, 127, 128, 300, usize::MAX] {
+            assert_eq!(read_varint(&mut reader), Some(value));
+        }
+        // a varint that never ends is rejected instead of overflowing
+        assert_eq!(read_varint(&mut [0xff; 20].as_slice()), None);
+    }
+}
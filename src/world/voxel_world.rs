@@ -1,9 +1,10 @@
 use super::{
-    chunked, pos2d::chunks_in_col, BlockPos, BlockPos2d, Chunk, ChunkPos, ChunkedPos, ColPos,
-    ColedPos, Realm, CHUNK_S1, MAX_HEIGHT, Y_CHUNKS,
+    chunked, biome::ColBiomes, heightmap::ColHeights, pos2d::chunks_in_col, BlockChangeCause, BlockChanged, BlockPos, BlockPos2d, Chunk, ChunkLight,
+    ChunkPos, ChunkedPos, ColPos, ColedPos, Heightmap, Realm, CHUNK_S1, MAX_HEIGHT, Y_CHUNKS,
 };
-use crate::Block;
+use crate::{block::BlockState, Block};
 use bevy::prelude::{Resource, Vec3};
+use crossbeam::queue::SegQueue;
 use dashmap::DashMap;
 use std::{
     ops::{Deref, DerefMut},
@@ -12,14 +13,30 @@ use std::{
 
 pub struct TrackedChunk {
     chunk: Chunk,
+    pub light: ChunkLight,
     pub changed: bool,
+    // edited since it was generated or loaded, needs to be saved on unload
+    pub edited: bool,
 }
 
 impl TrackedChunk {
     pub fn new() -> Self {
         Self {
             chunk: Chunk::new(),
+            light: ChunkLight::default(),
             changed: false,
+            edited: false,
+        }
+    }
+}
+
+impl From<Chunk> for TrackedChunk {
+    fn from(chunk: Chunk) -> Self {
+        Self {
+            chunk,
+            light: ChunkLight::default(),
+            changed: false,
+            edited: false,
         }
     }
 }
@@ -38,52 +55,59 @@ impl DerefMut for TrackedChunk {
     }
 }
 
-pub struct BlockRayCastHit {
-    pub pos: BlockPos,
-    pub normal: Vec3,
-}
-
-impl PartialEq for BlockRayCastHit {
-    fn eq(&self, other: &Self) -> bool {
-        self.pos == other.pos
-    }
-}
-
-#[derive(Resource)]
+#[derive(Resource, Clone)]
 pub struct VoxelWorld {
     pub chunks: Arc<DashMap<ChunkPos, TrackedChunk>>,
+    // heightmaps of the columns that are fully generated or loaded
+    pub heightmaps: Arc<DashMap<ColPos, ColHeights>>,
+    // biome maps of the loaded columns, only in realms that have biomes
+    pub biomes: Arc<DashMap<ColPos, ColBiomes>>,
+    // block changes waiting to be sent as BlockChanged events
+    pub changes: Arc<SegQueue<BlockChanged>>,
 }
 
 impl VoxelWorld {
     pub fn new() -> Self {
         VoxelWorld {
             chunks: Arc::new(DashMap::new()),
+            heightmaps: Arc::new(DashMap::new()),
+            biomes: Arc::new(DashMap::new()),
+            changes: Arc::new(SegQueue::new()),
         }
     }
 
-    pub fn new_with(chunks: Arc<DashMap<ChunkPos, TrackedChunk>>) -> Self {
-        VoxelWorld { chunks }
+    pub(super) fn publish_change(&self, pos: BlockPos, old_block: Block, new_block: Block, cause: BlockChangeCause) {
+        if old_block == new_block || cause == BlockChangeCause::Gen {
+            return;
+        }
+        self.changes.push(BlockChanged { pos, old_block, new_block, cause });
     }
 
-    pub fn set_block(&self, pos: BlockPos, block: Block) {
+    /// Sets the block in its default state
+    pub fn set_block(&self, pos: BlockPos, block: Block, cause: BlockChangeCause) {
+        self.set_block_with_state(pos, block, 0, cause);
+    }
+
+    pub fn set_block_with_state(&self, pos: BlockPos, block: Block, state: BlockState, cause: BlockChangeCause) {
         let (chunk_pos, chunked_pos) = <(ChunkPos, ChunkedPos)>::from(pos);
-        self.chunks
-            .entry(chunk_pos)
-            .or_insert_with(|| TrackedChunk::new())
-            .set(chunked_pos, block);
+        let old_block = {
+            let mut chunk = self.chunks
+                .entry(chunk_pos)
+                .or_insert_with(|| TrackedChunk::new());
+            let old_block = *chunk.get(chunked_pos);
+            chunk.set_with_state(chunked_pos, block, state);
+            old_block
+        };
+        self.update_heightmaps(pos, block);
         self.mark_change(chunk_pos, chunked_pos);
+        self.publish_change(pos, old_block, block, cause);
     }
 
-    pub fn set_block_safe(&self, pos: BlockPos, block: Block) -> bool {
+    pub fn set_block_safe(&self, pos: BlockPos, block: Block, cause: BlockChangeCause) -> bool {
         if pos.y < 0 || pos.y >= MAX_HEIGHT as i32 {
             return false;
         }
-        let (chunk_pos, chunked_pos) = <(ChunkPos, ChunkedPos)>::from(pos);
-        self.chunks
-            .entry(chunk_pos)
-            .or_insert_with(|| TrackedChunk::new())
-            .set(chunked_pos, block);
-        self.mark_change(chunk_pos, chunked_pos);
+        self.set_block(pos, block, cause);
         true
     }
 
@@ -115,7 +139,7 @@ impl VoxelWorld {
         }
     }
 
-    pub fn set_if_empty(&self, pos: BlockPos, block: Block) {
+    pub fn set_if_empty(&self, pos: BlockPos, block: Block, cause: BlockChangeCause) {
         let (chunk_pos, chunked_pos) = <(ChunkPos, ChunkedPos)>::from(pos);
         if self
             .chunks
@@ -123,7 +147,9 @@ impl VoxelWorld {
             .or_insert_with(|| TrackedChunk::new())
             .set_if_empty(chunked_pos, block)
         {
+            self.update_heightmaps(pos, block);
             self.mark_change(chunk_pos, chunked_pos);
+            self.publish_change(pos, Block::Air, block, cause);
         }
     }
 
@@ -135,6 +161,48 @@ impl VoxelWorld {
         }
     }
 
+    /// The state of the block at pos, 0 if it isn't loaded
+    pub fn get_state(&self, pos: BlockPos) -> BlockState {
+        if pos.y < 0 || pos.y >= MAX_HEIGHT as i32 {
+            return 0;
+        }
+        let (chunk_pos, chunked_pos) = <(ChunkPos, ChunkedPos)>::from(pos);
+        match self.chunks.get(&chunk_pos) {
+            None => 0,
+            Some(chunk) => chunk.get_state(chunked_pos),
+        }
+    }
+
+    /// Changes the state of the block at pos, without changing the block.
+    /// Returns false if the block isn't loaded or doesn't have this state.
+    /// Doesn't send a BlockChanged event since the block stays the same.
+    pub fn set_state(&self, pos: BlockPos, state: BlockState) -> bool {
+        if pos.y < 0 || pos.y >= MAX_HEIGHT as i32 {
+            return false;
+        }
+        let (chunk_pos, chunked_pos) = <(ChunkPos, ChunkedPos)>::from(pos);
+        {
+            let Some(mut chunk) = self.chunks.get_mut(&chunk_pos) else {
+                return false;
+            };
+            let block = *chunk.get(chunked_pos);
+            if state >= block.state_count() {
+                return false;
+            }
+            if chunk.get_state(chunked_pos) == state {
+                return true;
+            }
+            chunk.set_with_state(chunked_pos, block, state);
+        }
+        self.mark_change(chunk_pos, chunked_pos);
+        true
+    }
+
+    pub fn is_loaded(&self, pos: BlockPos) -> bool {
+        let (chunk_pos, _) = <(ChunkPos, ChunkedPos)>::from(pos);
+        self.chunks.contains_key(&chunk_pos)
+    }
+
     pub fn get_block_safe(&self, pos: BlockPos) -> Block {
         if pos.y < 0 || pos.y >= MAX_HEIGHT as i32 {
             Block::Air
@@ -144,38 +212,18 @@ impl VoxelWorld {
     }
 
     pub fn top_block(&self, pos: BlockPos2d) -> (Block, i32) {
-        let (col_pos, pos2d) = pos.into();
-        for y in (0..Y_CHUNKS as i32).rev() {
-            let chunk_pos = ChunkPos {
-                x: col_pos.x,
-                y,
-                z: col_pos.z,
-                realm: col_pos.realm,
-            };
-            if let Some(chunk) = self.chunks.get(&chunk_pos) {
-                let (block, block_y) = chunk.top(pos2d);
-                if *block != Block::Air {
-                    return (block.clone(), y * CHUNK_S1 as i32 + block_y as i32);
-                }
-            }
+        match self.height(pos, Heightmap::Surface) {
+            Some(height) if height > 0 => {
+                let y = height - 1;
+                (self.get_block(BlockPos { x: pos.x, y, z: pos.z, realm: pos.realm }), y)
+            },
+            _ => (Block::Air, 0),
         }
-        (Block::Air, 0)
     }
 
     pub fn is_col_loaded(&self, player_pos: Vec3, realm: Realm) -> bool {
-        let (chunk_pos, _): (ChunkPos, _) = <BlockPos>::from((player_pos, realm)).into();
-        for y in (0..Y_CHUNKS as i32).rev() {
-            let chunk = ChunkPos {
-                x: chunk_pos.x,
-                y,
-                z: chunk_pos.z,
-                realm: chunk_pos.realm,
-            };
-            if self.chunks.contains_key(&chunk) {
-                return true;
-            }
-        }
-        false
+        let col_pos = ColPos::from((player_pos, realm));
+        self.heightmaps.contains_key(&col_pos)
     }
 
     pub fn mark_change_col(&self, col_pos: ColPos) {
@@ -185,7 +233,24 @@ impl VoxelWorld {
         }
     }
 
+    pub fn mark_change_neighbour_cols(&self, col_pos: ColPos) {
+        // USED BY TERRAIN GEN so the neighbouring chunks cull the faces hidden by the new column,
+        // air chunks are skipped since they don't have a mesh anyway
+        for (dx, dz) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let neighbour = ColPos { x: col_pos.x + dx, z: col_pos.z + dz, realm: col_pos.realm };
+            for chunk_pos in chunks_in_col(&neighbour) {
+                if let Some(mut chunk) = self.chunks.get_mut(&chunk_pos) {
+                    if !matches!(**chunk, Chunk::Uniform(Block::Air)) {
+                        chunk.changed = true;
+                    }
+                }
+            }
+        }
+    }
+
     pub fn unload_col(&self, col: ColPos) {
+        self.heightmaps.remove(&col);
+        self.biomes.remove(&col);
         for y in 0..Y_CHUNKS as i32 {
             let chunk_pos = ChunkPos {
                 x: col.x,
@@ -203,6 +268,24 @@ impl VoxelWorld {
         }
     }
 
+    pub fn compact_col(&self, col_pos: ColPos) {
+        // USED BY TERRAIN GEN so uniform chunks don't hold voxel data
+        for chunk_pos in chunks_in_col(&col_pos) {
+            if let Some(mut chunk) = self.chunks.get_mut(&chunk_pos) {
+                chunk.compact();
+            }
+        }
+    }
+
+    pub fn clear_edited_col(&self, col_pos: ColPos) {
+        // USED BY TERRAIN GEN so freshly generated columns are not saved to disk
+        for chunk_pos in chunks_in_col(&col_pos) {
+            if let Some(mut chunk) = self.chunks.get_mut(&chunk_pos) {
+                chunk.edited = false;
+            }
+        }
+    }
+
     fn border_sign(coord: usize) -> i32 {
         if coord == 0 {
             -1
@@ -213,8 +296,28 @@ impl VoxelWorld {
         }
     }
 
+    /// The neighbouring chunks touching the block at chunked_pos, their meshes depend on it
+    pub(super) fn border_neighbours(chunk_pos: ChunkPos, chunked_pos: ChunkedPos) -> impl Iterator<Item = ChunkPos> {
+        [chunked_pos.0, chunked_pos.1, chunked_pos.2].into_iter().enumerate().filter_map(move |(axis, coord)| {
+            let border_sign = VoxelWorld::border_sign(coord);
+            if border_sign == 0 {
+                return None;
+            }
+            let mut neighbour = chunk_pos;
+            match axis {
+                0 => neighbour.x += border_sign,
+                1 => neighbour.y += border_sign,
+                _ => neighbour.z += border_sign,
+            }
+            Some(neighbour)
+        })
+    }
+
     fn mark_change(&self, chunk_pos: ChunkPos, chunked_pos: ChunkedPos) {
-        self.mark_change_single(chunk_pos);
+        if let Some(mut chunk) = self.chunks.get_mut(&chunk_pos) {
+            chunk.changed = true;
+            chunk.edited = true;
+        }
         // register change for neighboring chunks
         let border_sign_x = VoxelWorld::border_sign(chunked_pos.0);
         if border_sign_x != 0 {
@@ -237,76 +340,4 @@ impl VoxelWorld {
             self.mark_change_single(neighbor);
         }
     }
-
-    pub fn raycast(
-        &self,
-        realm: Realm,
-        start: Vec3,
-        dir: Vec3,
-        dist: f32,
-    ) -> Option<BlockRayCastHit> {
-        let mut pos = BlockPos {
-            realm,
-            x: start.x.floor() as i32,
-            y: start.y.floor() as i32,
-            z: start.z.floor() as i32,
-        };
-        let mut last_pos;
-        let sx = dir.x.signum() as i32;
-        let sy = dir.y.signum() as i32;
-        let sz = dir.z.signum() as i32;
-        if sx == 0 && sy == 0 && sz == 0 {
-            return None;
-        }
-        let next_x = (pos.x + sx.max(0)) as f32;
-        let next_y = (pos.y + sy.max(0)) as f32;
-        let next_z = (pos.z + sz.max(0)) as f32;
-        let mut t_max_x = (next_x - start.x) / dir.x;
-        let mut t_max_y = (next_y - start.y) / dir.y;
-        let mut t_max_z = (next_z - start.z) / dir.z;
-        let slope_x = 1. / dir.x.abs();
-        let slope_y = 1. / dir.y.abs();
-        let slope_z = 1. / dir.z.abs();
-        loop {
-            last_pos = pos;
-            if t_max_x < t_max_y {
-                if t_max_x < t_max_z {
-                    if t_max_x >= dist {
-                        return None;
-                    };
-                    pos.x += sx;
-                    t_max_x += slope_x;
-                } else {
-                    if t_max_z >= dist {
-                        return None;
-                    };
-                    pos.z += sz;
-                    t_max_z += slope_z;
-                }
-            } else if t_max_y < t_max_z {
-                if t_max_y >= dist {
-                    return None;
-                };
-                pos.y += sy;
-                t_max_y += slope_y;
-            } else {
-                if t_max_z >= dist {
-                    return None;
-                };
-                pos.z += sz;
-                t_max_z += slope_z;
-            }
-            if self.get_block_safe(pos).is_targetable() {
-                return Some(BlockRayCastHit {
-                    pos,
-                    normal: Vec3 {
-                        x: (last_pos.x - pos.x) as f32,
-                        y: (last_pos.y - pos.y) as f32,
-                        z: (last_pos.z - pos.z) as f32,
-                    },
-                });
-            }
-        }
-    }
 }
-